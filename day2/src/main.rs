@@ -1,13 +1,425 @@
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+/// A single handful of cubes shown by the elf, e.g. "3 blue, 4 red".
+/// Maps each colour name to the number of cubes of that colour.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Draw {
+    cubes: BTreeMap<String, u32>,
+}
+
+/// One line of input: the game id and all draws separated by ';'
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Game {
+    id: u32,
+    draws: Vec<Draw>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    MissingId(String),
+    MalformedChunk(String),
+    InvalidCount(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingId(line) => write!(f, "missing or invalid game id in {:?}", line),
+            ParseError::MalformedChunk(chunk) => write!(f, "malformed chunk {:?}, expected \"<count> <colour>\"", chunk),
+            ParseError::InvalidCount(count) => write!(f, "invalid cube count {:?}", count),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Draw {
+    type Err = ParseError;
+
+    /// Parses a draw like "1 red, 2 green, 6 blue".
+    /// A colour named more than once within one draw is summed up.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cubes = BTreeMap::new();
+        for part in s.split(',') {
+            let mut split = part.split_whitespace();
+            let (count, colour) = match (split.next(), split.next(), split.next()) {
+                (Some(count), Some(colour), None) => (count, colour),
+                _ => return Err(ParseError::MalformedChunk(part.trim().to_string())),
+            };
+            let count: u32 = count
+                .parse()
+                .map_err(|_| ParseError::InvalidCount(count.to_string()))?;
+            *cubes.entry(colour.to_string()).or_insert(0) += count;
+        }
+        Ok(Draw { cubes })
+    }
+}
+
+impl Game {
+    /// The fewest cubes of each colour that make this game possible,
+    /// i.e. the maximum count per colour over all draws
+    fn min_cubes(&self) -> BTreeMap<String, u32> {
+        self.draws.iter().fold(BTreeMap::new(), |mut maxima, draw| {
+            for (colour, &count) in &draw.cubes {
+                let max = maxima.entry(colour.clone()).or_insert(0);
+                *max = (*max).max(count);
+            }
+            maxima
+        })
+    }
+
+    /// Product of the minimum cube counts over the given colours,
+    /// a colour the game never shows counts as 0
+    fn power(&self, colours: &[String]) -> u64 {
+        let min_cubes = self.min_cubes();
+        colours
+            .iter()
+            .map(|colour| min_cubes.get(colour).copied().unwrap_or(0) as u64)
+            .product()
+    }
+}
+
+impl FromStr for Game {
+    type Err = ParseError;
+
+    /// Parses a full line like "Game 1: 3 blue, 4 red; 1 red, 2 green"
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let id = get_game_id(line).ok_or_else(|| ParseError::MissingId(line.to_string()))?;
+        let draws = process_line(line)
+            .into_iter()
+            .map(Draw::from_str)
+            .collect::<Result<Vec<Draw>, ParseError>>()?;
+        Ok(Game { id, draws })
+    }
+}
+
+/// Cube limits per colour the bag was loaded with, e.g. "12 red, 13 green, 14 blue".
+/// Colours not listed in the bag have a limit of zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bag {
+    limits: BTreeMap<String, u32>,
+}
+
+/// A colour in one draw of a game that exceeded the bag's limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Violation {
+    draw: usize,
+    colour: String,
+    count: u32,
+    limit: u32,
+}
+
+/// Command line options: the bags to check the games against,
+/// optionally the number of games the smallest admitting bag must allow,
+/// whether to print the statistics report and where to export the minimum cube sets
+#[derive(Debug, Default)]
+struct Options {
+    bags: Vec<Bag>,
+    min_games: Option<usize>,
+    stats: bool,
+    csv: Option<String>,
+    json: Option<String>,
+}
+
+/// Count of occurrences per cube count, per colour
+type Histograms = BTreeMap<String, BTreeMap<u32, usize>>;
+
+/// Aggregates over all parsed games
+#[derive(Debug, Default)]
+struct Stats {
+    /// how many games need a minimum of n cubes of a colour
+    min_bag: Histograms,
+    /// how often n cubes of a colour were shown in a single draw
+    draws: Histograms,
+    /// id and power of the game with the maximum power
+    max_power: Option<(u32, u64)>,
+}
+
+impl Bag {
+    fn limit(&self, colour: &str) -> u32 {
+        self.limits.get(colour).copied().unwrap_or(0)
+    }
+
+    /// Total number of cubes in the bag
+    fn size(&self) -> u64 {
+        self.limits.values().map(|&count| count as u64).sum()
+    }
+
+    /// All colours in all draws of the game that exceed this bag's limits
+    fn violations(&self, game: &Game) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (i, draw) in game.draws.iter().enumerate() {
+            for (colour, &count) in &draw.cubes {
+                let limit = self.limit(colour);
+                if count > limit {
+                    violations.push(Violation { draw: i + 1, colour: colour.clone(), count, limit });
+                }
+            }
+        }
+        violations
+    }
+
+    fn admits(&self, game: &Game) -> bool {
+        self.violations(game).is_empty()
+    }
+
+    /// Finds the bag with the fewest total cubes that makes at least `k` of the games possible.
+    /// Enumerates the candidate limits (the games' minimum counts) colour by colour,
+    /// so this is exact but grows with games^(colours - 1).
+    fn smallest_admitting(games: &[Game], k: usize) -> Option<Bag> {
+        if k == 0 || k > games.len() {
+            return None;
+        }
+        let needs: Vec<BTreeMap<String, u32>> = games.iter().map(Game::min_cubes).collect();
+        let colours: Vec<String> = needs
+            .iter()
+            .flat_map(|need| need.keys().cloned())
+            .collect::<std::collections::BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        let candidates: Vec<usize> = (0..games.len()).collect();
+        let mut best: Option<Bag> = None;
+        smallest_admitting_rec(&needs, &colours, k, &candidates, &mut BTreeMap::new(), &mut best);
+        best
+    }
+}
+
+/// Fixes the limit of one colour after the other, keeping only games that still fit.
+/// The last colour's limit is simply the k-th smallest need of the remaining games.
+fn smallest_admitting_rec(
+    needs: &[BTreeMap<String, u32>],
+    colours: &[String],
+    k: usize,
+    candidates: &[usize],
+    limits: &mut BTreeMap<String, u32>,
+    best: &mut Option<Bag>,
+) {
+    let need = |game: usize, colour: &str| needs[game].get(colour).copied().unwrap_or(0);
+    let (colour, rest) = match colours.split_first() {
+        Some(split) => split,
+        None => {
+            let bag = Bag { limits: limits.clone() };
+            if best.as_ref().is_none_or(|b| bag.size() < b.size()) {
+                *best = Some(bag);
+            }
+            return;
+        }
+    };
+
+    let mut values: Vec<u32> = candidates.iter().map(|&g| need(g, colour)).collect();
+    values.sort_unstable();
+    if rest.is_empty() {
+        // the k-th smallest need is the least limit admitting k of the remaining games
+        values = vec![values[k - 1]];
+    } else {
+        values.dedup();
+    }
+
+    for value in values {
+        let remaining: Vec<usize> = candidates.iter().copied().filter(|&g| need(g, colour) <= value).collect();
+        if remaining.len() < k {
+            continue;
+        }
+        limits.insert(colour.clone(), value);
+        smallest_admitting_rec(needs, rest, k, &remaining, limits, best);
+        limits.remove(colour);
+    }
+}
+
+impl Stats {
+    fn from_games(games: &[Game]) -> Stats {
+        let colours = all_colours(games);
+        let mut stats = Stats::default();
+        for game in games {
+            // a game that never shows a colour needs 0 cubes of it
+            let min_cubes = game.min_cubes();
+            for colour in &colours {
+                let count = min_cubes.get(colour).copied().unwrap_or(0);
+                *stats.min_bag.entry(colour.clone()).or_default().entry(count).or_insert(0) += 1;
+            }
+            for draw in &game.draws {
+                for (colour, &count) in &draw.cubes {
+                    *stats.draws.entry(colour.clone()).or_default().entry(count).or_insert(0) += 1;
+                }
+            }
+            let power = game.power(&colours);
+            if stats.max_power.is_none_or(|(_, max)| power > max) {
+                stats.max_power = Some((game.id, power));
+            }
+        }
+        stats
+    }
+
+    fn print(&self) {
+        println!("Minimum bag distribution per colour:");
+        print_histograms(&self.min_bag);
+        println!("Draw histograms per colour:");
+        print_histograms(&self.draws);
+        if let Some((id, power)) = self.max_power {
+            println!("Maximum power: {} (Game {})", power, id);
+        }
+    }
+}
+
+fn print_histograms(histograms: &Histograms) {
+    for (colour, histogram) in histograms {
+        let total: usize = histogram.values().sum();
+        let sum: u64 = histogram.iter().map(|(&count, &n)| count as u64 * n as u64).sum();
+        let min = histogram.keys().next().copied().unwrap_or(0);
+        let max = histogram.keys().next_back().copied().unwrap_or(0);
+        println!(
+            "  {}: min {}, max {}, mean {:.2}",
+            colour,
+            min,
+            max,
+            sum as f64 / total.max(1) as f64
+        );
+        for (count, n) in histogram {
+            println!("    {:>3}: {:<4} {}", count, n, "#".repeat(*n));
+        }
+    }
+}
+
+/// The colours of the puzzle, which count even when no game shows them
+const PUZZLE_COLOURS: [&str; 3] = ["red", "green", "blue"];
+
+/// The puzzle's colours and all colours appearing in any game,
+/// used for the power and as the columns of the exports
+fn all_colours(games: &[Game]) -> Vec<String> {
+    games
+        .iter()
+        .flat_map(|game| game.draws.iter().flat_map(|draw| draw.cubes.keys().cloned()))
+        .chain(PUZZLE_COLOURS.iter().map(|colour| colour.to_string()))
+        .collect::<std::collections::BTreeSet<String>>()
+        .into_iter()
+        .collect()
+}
+
+/// One row per game with its minimum cube count per colour and its power
+fn export_csv(games: &[Game]) -> String {
+    let colours = all_colours(games);
+    let header: Vec<String> = colours.iter().map(|colour| csv_escape(colour)).collect();
+    let mut out = format!("game,{},power\n", header.join(","));
+    for game in games {
+        let min_cubes = game.min_cubes();
+        let counts: Vec<String> = colours
+            .iter()
+            .map(|colour| min_cubes.get(colour).copied().unwrap_or(0).to_string())
+            .collect();
+        out += &format!("{},{},{}\n", game.id, counts.join(","), game.power(&colours));
+    }
+    out
+}
+
+/// An array of `{"game": id, "min_cubes": {colour: count, ...}, "power": n}` objects
+fn export_json(games: &[Game]) -> String {
+    let colours = all_colours(games);
+    let rows: Vec<String> = games
+        .iter()
+        .map(|game| {
+            let min_cubes = game.min_cubes();
+            let cubes: Vec<String> = colours
+                .iter()
+                .map(|colour| {
+                    let count = min_cubes.get(colour).copied().unwrap_or(0);
+                    format!("\"{}\": {}", json_escape(colour), count)
+                })
+                .collect();
+            format!(
+                "  {{\"game\": {}, \"min_cubes\": {{{}}}, \"power\": {}}}",
+                game.id,
+                cubes.join(", "),
+                game.power(&colours)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+/// Quotes a field containing a separator, quote or line break, doubling the quotes
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl FromStr for Bag {
+    type Err = ParseError;
+
+    /// A bag is written just like a draw: "12 red, 13 green, 14 blue"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let draw: Draw = s.parse()?;
+        Ok(Bag { limits: draw.cubes })
+    }
+}
+
+impl fmt::Display for Bag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.limits.iter().map(|(colour, count)| format!("{} {}", count, colour)).collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "draw {}: {} {} > {}", self.draw, self.count, self.colour, self.limit)
+    }
+}
+
+impl Options {
+    /// Parses `--bag "<spec>"` (repeatable), `--bags <file>` with one bag per line,
+    /// `--min-games <k>`, `--stats` and `--csv <file>` / `--json <file>` for the minimum cube sets.
+    /// Without any bag the puzzle's 12 red, 13 green, 14 blue is used.
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Options, Box<dyn std::error::Error>> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+            match arg.as_str() {
+                "--bag" => options.bags.push(value()?.parse()?),
+                "--bags" => {
+                    for line in std::fs::read_to_string(value()?)?.lines() {
+                        if !line.trim().is_empty() {
+                            options.bags.push(line.parse()?);
+                        }
+                    }
+                }
+                "--min-games" => options.min_games = Some(value()?.parse()?),
+                "--stats" => options.stats = true,
+                "--csv" => options.csv = Some(value()?),
+                "--json" => options.json = Some(value()?),
+                _ => return Err(format!("unknown argument {:?}", arg).into()),
+            }
+        }
+        if options.bags.is_empty() {
+            options.bags.push("12 red, 13 green, 14 blue".parse()?);
+        }
+        Ok(options)
+    }
+}
 
 fn main() -> std::io::Result<()> {
-    let max_red = 12;
-    let max_green = 13;
-    let max_blue = 14;
-    let mut sumpossible: usize = 0;
-    let mut min_power: u64 = 0;
+    let options = Options::from_args(env::args().skip(1))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
     let mut sum_powers: u64 = 0;
 
     // Get the current executable path
@@ -25,50 +437,71 @@ fn main() -> std::io::Result<()> {
     let file = File::open(exe_path).expect("file not found");
     let reader = BufReader::new(file);
 
-    // iterate over lines
+    let mut games: Vec<Game> = Vec::new();
     for line in reader.lines() {
-        let mut still_possible = true;
         let line = line?;
-        let game_id = get_game_id(line.as_str());
-        let processed_chunks = process_line(&line);
-        let results = process_chunks(processed_chunks);
-
-        println!("Game ID: {:?}", game_id);
-        for &(red, green, blue) in &results {
-            println!("Red: {}, Green: {}, Blue: {}", red, green, blue);
-            if red > max_red || green > max_green || blue > max_blue {
-                still_possible = false;
-            }
+        if line.trim().is_empty() {
+            continue;
         }
-        if still_possible {
-            sumpossible += game_id.unwrap() as usize;
-            println!("-> possible");
-        } else {
-            println!("-> not possible");
+        let game: Game = line
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        games.push(game);
+    }
+
+    // part one, for every bag
+    for bag in &options.bags {
+        println!("Bag: {}", bag);
+        let mut sumpossible: usize = 0;
+        for game in &games {
+            let violations = bag.violations(game);
+            if violations.is_empty() {
+                sumpossible += game.id as usize;
+                println!("Game {} -> possible", game.id);
+            } else {
+                let reasons: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                println!("Game {} -> not possible ({})", game.id, reasons.join("; "));
+            }
         }
+        let feasible = games.iter().filter(|game| bag.admits(game)).count();
+        println!("Feasible games: {}/{}", feasible, games.len());
+        println!("Sum of possible game IDs: {}", sumpossible);
+    }
 
-        // part two
-        // add up the number of red, green, and blue in one game
-        let (max_red, max_green, max_blue) = results.iter().fold(
-            (0, 0, 0),
-            |(max_red, max_green, max_blue), &(red, green, blue)| {
-                (max_red.max(red), max_green.max(green), max_blue.max(blue))
-            },
-        );
-        min_power = max_red as u64 * max_green as u64 * max_blue as u64;
-        println!("Min power: {}", min_power);
+    // part two
+    let colours = all_colours(&games);
+    for game in &games {
+        let min_power = game.power(&colours);
+        println!("Game {} min power: {}", game.id, min_power);
         sum_powers += min_power;
     }
-
-    println!("Sum of possible game IDs: {}", sumpossible);
     println!("Sum of powers: {}", sum_powers);
 
+    if options.stats {
+        Stats::from_games(&games).print();
+    }
+    if let Some(path) = &options.csv {
+        std::fs::write(path, export_csv(&games))?;
+        println!("Minimum cube sets written to {}", path);
+    }
+    if let Some(path) = &options.json {
+        std::fs::write(path, export_json(&games))?;
+        println!("Minimum cube sets written to {}", path);
+    }
+
+    if let Some(k) = options.min_games {
+        match Bag::smallest_admitting(&games, k) {
+            Some(bag) => println!("Smallest bag admitting {} games: {} ({} cubes)", k, bag, bag.size()),
+            None => println!("No bag admits {} of {} games", k, games.len()),
+        }
+    }
+
     Ok(())
 }
 
-fn get_game_id(line: &str) -> Option<i32> {
+fn get_game_id(line: &str) -> Option<u32> {
     line.split_once(':')
-        .and_then(|(left, _)| left.split_whitespace().nth(1)?.parse::<i32>().ok())
+        .and_then(|(left, _)| left.split_whitespace().nth(1)?.parse::<u32>().ok())
 }
 
 fn process_line(line: &str) -> Vec<&str> {
@@ -81,30 +514,204 @@ fn process_line(line: &str) -> Vec<&str> {
     chunks
 }
 
-fn process_chunks(chunks: Vec<&str>) -> Vec<(i32, i32, i32)> {
-    let mut results = Vec::new();
-
-    for chunk in chunks {
-        let parts = chunk.split(',').collect::<Vec<&str>>();
-        let mut red_count = 0;
-        let mut green_count = 0;
-        let mut blue_count = 0;
-
-        for part in parts {
-            let color_parts = part.trim().split_whitespace().collect::<Vec<&str>>();
-            if color_parts.len() == 2 {
-                let count: i32 = color_parts[0].parse().unwrap_or(0);
-                match color_parts[1] {
-                    "red" => red_count = count,
-                    "green" => green_count = count,
-                    "blue" => blue_count = count,
-                    _ => {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(cubes: &[(&str, u32)]) -> Draw {
+        Draw { cubes: cubes.iter().map(|&(colour, count)| (colour.to_string(), count)).collect() }
+    }
+
+    fn colours(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_games() {
+        let game: Game = "Game 12: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green".parse().unwrap();
+        assert_eq!(game.id, 12);
+        assert_eq!(
+            game.draws,
+            vec![draw(&[("blue", 3), ("red", 4)]), draw(&[("red", 1), ("green", 2), ("blue", 6)]), draw(&[("green", 2)])]
+        );
+        assert_eq!(game.min_cubes(), draw(&[("red", 4), ("green", 2), ("blue", 6)]).cubes);
+        assert_eq!(game.power(&colours(&PUZZLE_COLOURS)), 48);
+    }
+
+    #[test]
+    fn repeated_colour_within_a_draw_is_summed() {
+        assert_eq!("1 red, 2 blue, 3 red".parse::<Draw>(), Ok(draw(&[("red", 4), ("blue", 2)])));
+    }
+
+    #[test]
+    fn extra_colours() {
+        let game: Game = "Game 1: 3 blue, 4 red, 2 yellow; 1 green".parse().unwrap();
+        assert_eq!(game.min_cubes().get("yellow"), Some(&2));
+        assert_eq!(game.power(&colours(&PUZZLE_COLOURS)), 12);
+        assert_eq!(game.power(&colours(&["blue", "green", "red", "yellow"])), 24);
+        assert_eq!(all_colours(&[game]), colours(&["blue", "green", "red", "yellow"]));
+    }
+
+    #[test]
+    fn missing_colour_has_power_zero() {
+        let game: Game = "Game 1: 3 blue, 4 red".parse().unwrap();
+        assert_eq!(game.power(&all_colours(std::slice::from_ref(&game))), 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "Game 1: 3 blue, red".parse::<Game>(),
+            Err(ParseError::MalformedChunk("red".to_string()))
+        );
+        assert_eq!(
+            "Game 1: 3 light blue".parse::<Game>(),
+            Err(ParseError::MalformedChunk("3 light blue".to_string()))
+        );
+        assert_eq!("Game 1: x blue".parse::<Game>(), Err(ParseError::InvalidCount("x".to_string())));
+        assert_eq!("Game 1: -3 blue".parse::<Game>(), Err(ParseError::InvalidCount("-3".to_string())));
+        assert_eq!("Game: 3 blue".parse::<Game>(), Err(ParseError::MissingId("Game: 3 blue".to_string())));
+        assert_eq!("Game 1 3 blue".parse::<Game>(), Err(ParseError::MissingId("Game 1 3 blue".to_string())));
+    }
+
+    const SAMPLE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+    fn sample() -> Vec<Game> {
+        SAMPLE.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn sample_bag() {
+        let bag: Bag = "12 red, 13 green, 14 blue".parse().unwrap();
+        let ids: u32 = sample().iter().filter(|game| bag.admits(game)).map(|game| game.id).sum();
+        assert_eq!(ids, 8);
+        let powers: u64 = sample().iter().map(|game| game.power(&colours(&PUZZLE_COLOURS))).sum();
+        assert_eq!(powers, 2286);
+    }
+
+    #[test]
+    fn violations_name_draw_colour_and_limit() {
+        let bag: Bag = "12 red, 13 green, 14 blue".parse().unwrap();
+        let violation = |draw, colour: &str, count, limit| Violation { draw, colour: colour.to_string(), count, limit };
+        let games = sample();
+        assert_eq!(bag.violations(&games[0]), vec![]);
+        assert_eq!(bag.violations(&games[2]), vec![violation(1, "red", 20, 12)]);
+        assert_eq!(bag.violations(&games[3]), vec![violation(3, "blue", 15, 14), violation(3, "red", 14, 12)]);
+        // colours missing from the bag have a limit of 0
+        let game: Game = "Game 9: 1 red; 2 yellow".parse().unwrap();
+        assert_eq!(bag.violations(&game), vec![violation(2, "yellow", 2, 0)]);
+        assert_eq!(violation(2, "yellow", 2, 0).to_string(), "draw 2: 2 yellow > 0");
+    }
+
+    /// Smallest bag size admitting k games by trying every bag with up to 4 cubes per colour
+    fn brute_force_size(games: &[Game], k: usize) -> Option<u64> {
+        let mut best = None;
+        for limits in 0..5u32.pow(3) {
+            let bag = Bag {
+                limits: PUZZLE_COLOURS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, colour)| (colour.to_string(), limits / 5u32.pow(i as u32) % 5))
+                    .collect(),
+            };
+            if games.iter().filter(|game| bag.admits(game)).count() >= k && best.is_none_or(|b| bag.size() < b) {
+                best = Some(bag.size());
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn smallest_admitting_matches_brute_force() {
+        // xorshift64 with a fixed seed
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        for _ in 0..300 {
+            let mut games = Vec::new();
+            for id in 1..=next(6) as u32 {
+                let mut draws = Vec::new();
+                for _ in 0..1 + next(3) {
+                    let mut cubes = BTreeMap::new();
+                    for colour in PUZZLE_COLOURS {
+                        if next(3) > 0 {
+                            cubes.insert(colour.to_string(), next(5) as u32);
+                        }
+                    }
+                    draws.push(Draw { cubes });
+                }
+                games.push(Game { id, draws });
+            }
+            for k in 0..=games.len() + 1 {
+                let bag = Bag::smallest_admitting(&games, k);
+                if k == 0 || k > games.len() {
+                    assert_eq!(bag, None);
+                    continue;
                 }
+                let bag = bag.unwrap();
+                assert!(games.iter().filter(|game| bag.admits(game)).count() >= k, "{:?} {}", games, k);
+                assert_eq!(Some(bag.size()), brute_force_size(&games, k), "{:?} {}", games, k);
             }
         }
+    }
+
+    #[test]
+    fn smallest_admitting_sample() {
+        let games = sample();
+        let bag = Bag::smallest_admitting(&games, 5).unwrap();
+        assert_eq!(bag.to_string(), "15 blue, 13 green, 20 red");
+        // games 1, 2 and 5 need at most 6 blue, 3 green, 6 red
+        assert_eq!(Bag::smallest_admitting(&games, 3).unwrap().size(), 15);
+    }
+
+    fn two_games() -> Vec<Game> {
+        vec![
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue".parse().unwrap(),
+            "Game 7: 2 red, 1 \"sky\\blue\"; 2 red".parse().unwrap(),
+        ]
+    }
 
-        results.push((red_count, green_count, blue_count));
+    #[test]
+    fn stats() {
+        let stats = Stats::from_games(&two_games());
+        let histogram = |entries: &[(u32, usize)]| entries.iter().copied().collect::<BTreeMap<u32, usize>>();
+        // game 7 never shows blue or green and needs 0 of them
+        assert_eq!(stats.min_bag["blue"], histogram(&[(0, 1), (6, 1)]));
+        assert_eq!(stats.min_bag["green"], histogram(&[(0, 1), (2, 1)]));
+        assert_eq!(stats.min_bag["red"], histogram(&[(2, 1), (4, 1)]));
+        assert_eq!(stats.min_bag["\"sky\\blue\""], histogram(&[(0, 1), (1, 1)]));
+        assert_eq!(stats.draws["red"], histogram(&[(1, 1), (2, 2), (4, 1)]));
+        assert_eq!(stats.draws.get("green"), Some(&histogram(&[(2, 1)])));
+        // both powers are 0 with the extra colour, the first game is kept
+        assert_eq!(stats.max_power, Some((1, 0)));
     }
 
-    results
+    #[test]
+    fn csv_export() {
+        assert_eq!(
+            export_csv(&two_games()),
+            "game,\"\"\"sky\\blue\"\"\",blue,green,red,power\n1,0,6,2,4,0\n7,1,0,0,2,0\n"
+        );
+        let games: Vec<Game> = vec!["Game 3: 1 red, 2 green, 3 blue".parse().unwrap()];
+        assert_eq!(export_csv(&games), "game,blue,green,red,power\n3,3,2,1,6\n");
+        assert_eq!(csv_escape("dark,red"), "\"dark,red\"");
+    }
+
+    #[test]
+    fn json_export() {
+        assert_eq!(
+            export_json(&two_games()),
+            "[\n  {\"game\": 1, \"min_cubes\": {\"\\\"sky\\\\blue\\\"\": 0, \"blue\": 6, \"green\": 2, \"red\": 4}, \"power\": 0},\n  \
+             {\"game\": 7, \"min_cubes\": {\"\\\"sky\\\\blue\\\"\": 1, \"blue\": 0, \"green\": 0, \"red\": 2}, \"power\": 0}\n]\n"
+        );
+        assert_eq!(json_escape("a\tb"), "a\\u0009b");
+    }
 }
@@ -1,4 +1,7 @@
+use std::env;
+use std::fmt;
 use std::fs::{read_to_string};
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 struct Coordinate {
@@ -14,14 +17,14 @@ struct Number {
 }
 #[derive(Debug, Clone)] 
 struct Symbol {
-        char: u8,
+        char: char,
         coordinate: Coordinate,
         // length is always one
 }
 
 impl Number {
     fn new(chars: String, coordinate: Coordinate) -> Number {
-        let len = chars.len();
+        let len = chars.chars().count();
         Number {
             chars: chars.clone(),
             coordinate,
@@ -29,104 +32,401 @@ impl Number {
         }
     }
 
-    /// Check if the Symbol is a neighbour of the Number
-    /// A neighbour is a Symbol that is one step away in any direction
-    /// also diagonally(!)
-    fn is_neighbour(&self, sym: &Symbol) -> bool {
-        // x coord must differ at most 1 at each end
-        let x_diff_min = (sym.coordinate.x - self.coordinate.x).abs();
-        let x_diff_max = (sym.coordinate.x - (self.coordinate.x + self.length as i32 - 1)).abs();
-        // both coordinates must differ at most 1
-        let y_diff = (sym.coordinate.y - self.coordinate.y).abs();
-        if (x_diff_min.min(x_diff_max) <= 1) && y_diff <= 1 {
-            return true;
+    fn value(&self) -> u64 {
+        self.chars.parse::<u64>().unwrap()
+    }
+
+    /// Cells around the Number in reading order: the row above,
+    /// both ends on its own row and the row below, diagonals included(!)
+    fn surrounding_cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let (x0, x1) = (self.coordinate.x - 1, self.coordinate.x + self.length as i32);
+        let y = self.coordinate.y;
+        (y - 1..=y + 1).flat_map(move |cy| {
+            (x0..=x1)
+                .filter(move |&cx| cy != y || cx == x0 || cx == x1)
+                .map(move |cx| (cx, cy))
+        })
+    }
+}
+
+/// Grid over the schematic that holds the index of the Symbol in each cell (if any),
+/// so that finding the neighbours of a Number only touches the cells around it
+struct SymbolGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<usize>>,
+}
+
+impl SymbolGrid {
+    fn new(symbols: &[Symbol]) -> SymbolGrid {
+        let width = symbols.iter().map(|s| s.coordinate.x as usize + 1).max().unwrap_or(0);
+        let height = symbols.iter().map(|s| s.coordinate.y as usize + 1).max().unwrap_or(0);
+        let mut cells = vec![None; width * height];
+        for (i, symbol) in symbols.iter().enumerate() {
+            cells[symbol.coordinate.y as usize * width + symbol.coordinate.x as usize] = Some(i);
+        }
+        SymbolGrid { width, height, cells }
+    }
+
+    fn get(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        self.cells[y as usize * self.width + x as usize]
+    }
+
+    /// Indices of all Symbols adjacent to the Number, in ascending order
+    fn neighbours(&self, number: &Number) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = number
+            .surrounding_cells()
+            .filter_map(|(x, y)| self.get(x, y))
+            .collect();
+        neighbours.sort_unstable();
+        neighbours
+    }
+}
+
+/// Bipartite adjacency between Numbers and Symbols, by index into their vectors.
+/// Every number links to all of its adjacent symbols and vice versa, so the sums
+/// derived from it do not depend on the order numbers or symbols are visited in.
+struct Adjacency {
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
+}
+
+impl Adjacency {
+    fn new(numbers: &[Number], symbol_count: usize, grid: &SymbolGrid) -> Adjacency {
+        let number_symbols: Vec<Vec<usize>> = numbers.iter().map(|number| grid.neighbours(number)).collect();
+        let mut symbol_numbers = vec![Vec::new(); symbol_count];
+        for (n, symbols) in number_symbols.iter().enumerate() {
+            for &s in symbols {
+                symbol_numbers[s].push(n);
+            }
+        }
+        Adjacency { number_symbols, symbol_numbers }
+    }
+
+    /// Sum of all part numbers, i.e. numbers adjacent to at least one symbol.
+    /// A number next to several symbols is still only counted once.
+    fn part_sum(&self, numbers: &[Number]) -> u64 {
+        numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number.value())
+            .sum()
+    }
+
+    /// Applies the rule to every matching symbol and sums up the reduced adjacent numbers
+    fn apply(&self, rule: &GearRule, numbers: &[Number], symbols: &[Symbol]) -> u64 {
+        symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(|(symbol, adjacent)| rule.matches(symbol, adjacent.len()))
+            .map(|(_, adjacent)| rule.reducer.reduce(adjacent.iter().map(|&n| numbers[n].value())))
+            .sum()
+    }
+}
+
+/// How the numbers adjacent to a symbol are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reducer {
+    Product,
+    Sum,
+    Max,
+    Min,
+}
+
+impl Reducer {
+    fn reduce(&self, values: impl Iterator<Item = u64>) -> u64 {
+        match self {
+            Reducer::Product => values.product(),
+            Reducer::Sum => values.sum(),
+            Reducer::Max => values.max().unwrap_or(0),
+            Reducer::Min => values.min().unwrap_or(0),
+        }
+    }
+}
+
+/// Which symbols a rule applies to and how their adjacent numbers are reduced.
+/// `None` for the symbol or the neighbour count means any.
+/// The puzzle's gears are any symbol with exactly two numbers, multiplied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GearRule {
+    symbol: Option<char>,
+    neighbours: Option<usize>,
+    reducer: Reducer,
+}
+
+impl GearRule {
+    fn gear() -> GearRule {
+        GearRule { symbol: None, neighbours: Some(2), reducer: Reducer::Product }
+    }
+
+    fn matches(&self, symbol: &Symbol, neighbours: usize) -> bool {
+        self.symbol.is_none_or(|c| c == symbol.char)
+            && self.neighbours.is_none_or(|n| n == neighbours)
+            // symbols without any adjacent number are never reduced
+            && neighbours > 0
+    }
+}
+
+impl FromStr for Reducer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "product" => Ok(Reducer::Product),
+            "sum" => Ok(Reducer::Sum),
+            "max" => Ok(Reducer::Max),
+            "min" => Ok(Reducer::Min),
+            _ => Err(format!("unknown reducer {:?}, expected product, sum, max or min", s)),
         }
-        return false;
     }
 }
 
+impl FromStr for GearRule {
+    type Err = String;
+
+    /// Parses "<symbol> <neighbours> <reducer>" where symbol and neighbours may be "any",
+    /// e.g. "* 3 product" or "any any sum"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let [symbol, neighbours, reducer] = parts.as_slice() else {
+            return Err(format!("invalid rule {:?}, expected \"<symbol> <neighbours> <reducer>\"", s));
+        };
+        let symbol = match *symbol {
+            "any" => None,
+            sym => {
+                let mut chars = sym.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => return Err(format!("invalid symbol {:?} in rule {:?}", sym, s)),
+                }
+            }
+        };
+        let neighbours = match *neighbours {
+            "any" => None,
+            n => Some(n.parse().map_err(|_| format!("invalid neighbour count {:?} in rule {:?}", n, s))?),
+        };
+        Ok(GearRule { symbol, neighbours, reducer: reducer.parse()? })
+    }
+}
+
+impl fmt::Display for GearRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.symbol {
+            Some(c) => write!(f, "'{}'", c)?,
+            None => write!(f, "any symbol")?,
+        }
+        match self.neighbours {
+            Some(n) => write!(f, " with exactly {} numbers", n)?,
+            None => write!(f, " with any numbers")?,
+        }
+        write!(f, ", {:?}", self.reducer)
+    }
+}
 
 fn main() -> std::io::Result<()> {
     let input_file = "input.txt";
 
-    let mut numbers: Vec<Number> = Vec::with_capacity(2048);
-    let mut symbols: Vec<Symbol> = Vec::with_capacity(2048);
-    // parts are only those Numbers/Symbols that match some rules
-    let mut partsum: u64 = 0;
+    // additional rules can be given as `--rule "<symbol> <neighbours> <reducer>"`,
+    // `--render` prints the annotated schematic and `--html <file>` exports it
+    let mut rules = vec![GearRule::gear()];
+    let mut render_ansi = false;
+    let mut html_file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        let result = match arg.as_str() {
+            "--render" => {
+                render_ansi = true;
+                Ok(())
+            }
+            "--rule" => value().and_then(|rule| rule.parse::<GearRule>()).map(|rule| rules.push(rule)),
+            "--html" => value().map(|file| html_file = Some(file)),
+            _ => Err(format!("unknown argument {:?}", arg)),
+        };
+        result.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
 
     println!("Input file: {:?}", input_file);
     let file_str = read_to_string(input_file).expect("Failed to read file");
+    let (numbers, symbols) = parse_schematic(&file_str);
+
+    // Now we have all the numbers and symbols and need to
+    // check for adjacency in the sorrounding lines
+    let grid = SymbolGrid::new(&symbols);
+    let adjacency = Adjacency::new(&numbers, symbols.len(), &grid);
 
-    file_str.split("\n")
+    for (number, neighbours) in numbers.iter().zip(&adjacency.number_symbols) {
+        for &i in neighbours {
+            println!("Symbol {} is neighbour of Number {}", symbols[i].char, number.chars);
+        }
+    }
+
+    let partsum = adjacency.part_sum(&numbers);
+    let gear_ratios_sum = adjacency.apply(&rules[0], &numbers, &symbols);
+    println!("Part number sum: {}", partsum); //part1
+    println!("Part gear ratios sum: {}", gear_ratios_sum); //part2
+    for rule in &rules[1..] {
+        println!("Rule {}: {}", rule, adjacency.apply(rule, &numbers, &symbols));
+    }
+
+    if render_ansi {
+        print!("{}", render(&numbers, &symbols, &adjacency, &rules[0], Format::Ansi));
+    }
+    if let Some(file) = html_file {
+        std::fs::write(&file, render(&numbers, &symbols, &adjacency, &rules[0], Format::Html))?;
+        println!("Annotated schematic written to {}", file);
+    }
+
+    Ok(())
+}
+
+
+/// What a cell of the rendered schematic shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    PartNumber,
+    OtherNumber,
+    Symbol,
+    Gear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ansi,
+    Html,
+}
+
+impl Cell {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Cell::Empty => "\x1b[2m",
+            Cell::PartNumber => "\x1b[32m",
+            Cell::OtherNumber => "\x1b[90m",
+            Cell::Symbol => "\x1b[33m",
+            Cell::Gear => "\x1b[1;31m",
+        }
+    }
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            Cell::Empty => "empty",
+            Cell::PartNumber => "part",
+            Cell::OtherNumber => "other",
+            Cell::Symbol => "symbol",
+            Cell::Gear => "gear",
+        }
+    }
+}
+
+/// Rebuilds the schematic from the parsed Numbers and Symbols and highlights
+/// part numbers, numbers without a symbol, symbols and the symbols matching the gear rule
+fn render(numbers: &[Number], symbols: &[Symbol], adjacency: &Adjacency, gear: &GearRule, format: Format) -> String {
+    let width = numbers
+        .iter()
+        .map(|n| n.coordinate.x as usize + n.length)
+        .chain(symbols.iter().map(|s| s.coordinate.x as usize + 1))
+        .max()
+        .unwrap_or(0);
+    let height = numbers
+        .iter()
+        .map(|n| n.coordinate.y)
+        .chain(symbols.iter().map(|s| s.coordinate.y))
+        .max()
+        .map_or(0, |y| y as usize + 1);
+
+    let mut rows = vec![vec![('.', Cell::Empty); width]; height];
+    for (number, neighbours) in numbers.iter().zip(&adjacency.number_symbols) {
+        let cell = if neighbours.is_empty() { Cell::OtherNumber } else { Cell::PartNumber };
+        for (i, c) in number.chars.chars().enumerate() {
+            rows[number.coordinate.y as usize][number.coordinate.x as usize + i] = (c, cell);
+        }
+    }
+    for (symbol, adjacent) in symbols.iter().zip(&adjacency.symbol_numbers) {
+        let cell = if gear.matches(symbol, adjacent.len()) { Cell::Gear } else { Cell::Symbol };
+        rows[symbol.coordinate.y as usize][symbol.coordinate.x as usize] = (symbol.char, cell);
+    }
+
+    let mut out = String::new();
+    if format == Format::Html {
+        out += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n";
+        out += "pre { background: #0f0f23; color: #cccccc; }\n";
+        out += ".empty { color: #555555; } .part { color: #00cc00; } .other { color: #888888; }\n";
+        out += ".symbol { color: #ffff66; } .gear { color: #ff4444; font-weight: bold; }\n";
+        out += "</style>\n</head>\n<body>\n<pre>\n";
+    }
+    for row in rows {
+        // group runs of the same kind of cell to keep the output small
+        let mut cells = row.into_iter().peekable();
+        while let Some((c, cell)) = cells.next() {
+            let mut run = c.to_string();
+            while let Some(&(next, _)) = cells.peek().filter(|(_, next_cell)| *next_cell == cell) {
+                run.push(next);
+                cells.next();
+            }
+            match format {
+                Format::Ansi => out += &format!("{}{}\x1b[0m", cell.ansi(), run),
+                Format::Html => out += &format!("<span class=\"{}\">{}</span>", cell.css_class(), html_escape(&run)),
+            }
+        }
+        out.push('\n');
+    }
+    if format == Format::Html {
+        out += "</pre>\n</body>\n</html>\n";
+    }
+    out
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Finds all Numbers and Symbols in the schematic, '.' being empty space
+fn parse_schematic(file_str: &str) -> (Vec<Number>, Vec<Symbol>) {
+    let mut numbers: Vec<Number> = Vec::with_capacity(2048);
+    let mut symbols: Vec<Symbol> = Vec::with_capacity(2048);
+
+    // x coordinates are char columns, slicing uses the byte offsets of the same chars
+    // so that symbols outside of ASCII neither break nor shift the schematic
+    file_str.lines()
     .enumerate()
     .for_each(|(y, line)| {
+        // (column, byte offset) where the current number started
         let mut start_index = None;
-        
-        for (x, c) in line.chars().enumerate() {
-            if c.is_digit(10) {
+
+        for (x, (i, c)) in line.char_indices().enumerate() {
+            if c.is_ascii_digit() {
                 if start_index.is_none() {
-                    start_index = Some(x);
+                    start_index = Some((x, i));
                 }
             } else {
-                if let Some(start) = start_index {
-                    process_group(start, x, y, &line[start..x], &mut numbers, &mut symbols);
+                if let Some((start, start_byte)) = start_index {
+                    process_group(start, y, &line[start_byte..i], &mut numbers, &mut symbols);
                     start_index = None;
                 }
                 if c != '.' {
-                    process_group(x, x+1, y, &line[x..x+1], &mut numbers, &mut symbols);
+                    process_group(x, y, &line[i..i + c.len_utf8()], &mut numbers, &mut symbols);
                 }
             }
         }
 
-        if let Some(start) = start_index {
-            process_group(start, line.len(), y, &line[start..], &mut numbers, &mut symbols);
+        if let Some((start, start_byte)) = start_index {
+            process_group(start, y, &line[start_byte..], &mut numbers, &mut symbols);
         }
     });
 
-    // Now we have all the numbers and symbols and need to 
-    // check for adjacency in the sorrounding lines
-    let mut gear_ratios_sum: u64 = 0;
-    // a symbol can have at most 6 adjacent numbers (diagonally counts as well)
-    // we will add the adjacent numbers per symbol index to this nested vec
-    let mut adjacent_numbers: Vec<Vec<u64>> = Vec::with_capacity(symbols.len());
-    for _ in 0..adjacent_numbers.capacity() {
-        adjacent_numbers.push(Vec::new());
-    }
-    
-    // TODO apparently result changes depening on outer/inner loop order
-    for number in &numbers {
-        for (i, symbol) in symbols.iter().enumerate() {
-            if number.is_neighbour(symbol) {
-                println!("Symbol {} is neighbour of Number {}", symbol.char as char, number.chars);
-                partsum += number.chars.parse::<u64>().unwrap();
-
-                // add adjacent number to the symbol's adjacent_numbers vector (as u64)
-                adjacent_numbers[i].push(number.chars.parse::<u64>().unwrap());
-
-                // break to avoid counting it twice when we get to the symbol's own line
-                break;
-            }
-        }
-    }
-
-
-    for nums in &adjacent_numbers {
-        if nums.len() == 2 {
-            gear_ratios_sum += nums[0] * nums[1];
-        }
-    }
-    println!("Part number sum: {}", partsum); //part1
-    println!("Part gear ratios sum: {}", gear_ratios_sum); //part2
-
-    Ok(())
+    (numbers, symbols)
 }
 
-
 /// Processes a group of characters (separated/grouped by '.').
 /// Groups can either be an integer number or a special character symbol (length 1).
 /// Identified items are added to either the numbers or symbols vector
-fn process_group(start: usize, end: usize, y: usize, group: &str, numbers: &mut Vec<Number>, symbols: &mut Vec<Symbol>) {
-    if group.chars().all(|c| c.is_digit(10)) && !group.is_empty() {
+fn process_group(start: usize, y: usize, group: &str, numbers: &mut Vec<Number>, symbols: &mut Vec<Symbol>) {
+    if group.chars().all(|c| c.is_ascii_digit()) && !group.is_empty() {
         // Group is a number
         let number = Number::new(group.to_string(), Coordinate { x: start as i32, y: y as i32 });
         println!("Found Number: {}", number.chars);
@@ -134,11 +434,77 @@ fn process_group(start: usize, end: usize, y: usize, group: &str, numbers: &mut
     } else {
         // Process each character in the group as a symbol
         for (i, ch) in group.chars().enumerate() {
-            if !ch.is_digit(10) {
-                let symbol = Symbol { char: ch as u8, coordinate: Coordinate { x: (start + i) as i32, y: y as i32 } };
-                println!("Found Symbol: {}", symbol.char as char);
+            if !ch.is_ascii_digit() {
+                let symbol = Symbol { char: ch, coordinate: Coordinate { x: (start + i) as i32, y: y as i32 } };
+                println!("Found Symbol: {}", symbol.char);
                 symbols.push(symbol);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..\n";
+
+    /// Pairwise reference check: the symbol lies in the box one cell around the number
+    fn is_neighbour(number: &Number, sym: &Symbol) -> bool {
+        let (x, y) = (number.coordinate.x, number.coordinate.y);
+        (sym.coordinate.y - y).abs() <= 1 && x - 1 <= sym.coordinate.x && sym.coordinate.x <= x + number.length as i32
+    }
+
+    #[test]
+    fn grid_neighbours_match_pairwise_check() {
+        // 12345 has symbols two cells left and right of it (not neighbours), one above-left
+        // of its start and one under its middle. 123456 has symbols diagonal to both ends,
+        // above and below, one above its middle and two more two cells beyond its corners
+        let schematic = format!(
+            "{}#.12345.*.\n.!..&...?.\n..123456..\n.~......^.\n~........~\n",
+            SAMPLE
+        );
+        let (numbers, symbols) = parse_schematic(&schematic);
+        let grid = SymbolGrid::new(&symbols);
+        for number in &numbers {
+            let pairwise: Vec<usize> = (0..symbols.len()).filter(|&i| is_neighbour(number, &symbols[i])).collect();
+            assert_eq!(grid.neighbours(number), pairwise, "number {} at {:?}", number.chars, number.coordinate);
+        }
+
+        let neighbour_chars = |chars: &str| -> String {
+            let number = numbers.iter().find(|n| n.chars == chars).unwrap();
+            grid.neighbours(number).iter().map(|&i| symbols[i].char).collect()
+        };
+        assert_eq!(neighbour_chars("12345"), "!&");
+        assert_eq!(neighbour_chars("123456"), "!&?~^");
+    }
+
+    #[test]
+    fn non_ascii_symbols_take_one_column() {
+        let (numbers, symbols) = parse_schematic("é12€..\n..ß.34\n");
+        let positions = |items: Vec<(String, &Coordinate)>| -> Vec<(String, i32, i32)> {
+            items.into_iter().map(|(item, c)| (item, c.x, c.y)).collect()
+        };
+        assert_eq!(
+            positions(numbers.iter().map(|n| (n.chars.clone(), &n.coordinate)).collect()),
+            vec![("12".to_string(), 1, 0), ("34".to_string(), 4, 1)]
+        );
+        assert_eq!(
+            positions(symbols.iter().map(|s| (s.char.to_string(), &s.coordinate)).collect()),
+            vec![("é".to_string(), 0, 0), ("€".to_string(), 3, 0), ("ß".to_string(), 2, 1)]
+        );
+        let grid = SymbolGrid::new(&symbols);
+        assert_eq!(grid.neighbours(&numbers[0]), vec![0, 1, 2]);
+        assert_eq!(grid.neighbours(&numbers[1]), vec![1]);
+        let adjacency = Adjacency::new(&numbers, symbols.len(), &grid);
+        assert_eq!(adjacency.part_sum(&numbers), 46);
+    }
+
+    #[test]
+    fn sample_sums() {
+        let (numbers, symbols) = parse_schematic(SAMPLE);
+        let adjacency = Adjacency::new(&numbers, symbols.len(), &SymbolGrid::new(&symbols));
+        assert_eq!(adjacency.part_sum(&numbers), 4361);
+        assert_eq!(adjacency.apply(&GearRule::gear(), &numbers, &symbols), 467835);
+    }
+}
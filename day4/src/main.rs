@@ -1,13 +1,263 @@
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
-use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+/// One scratchcard: "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53"
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Card {
+    id: u32,
+    winning: HashSet<u32>,
+    ours: HashSet<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    MissingId(String),
+    MissingSeparator(String),
+    InvalidNumber(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingId(line) => write!(f, "missing or invalid card id in {:?}", line),
+            ParseError::MissingSeparator(line) => write!(f, "missing \" | \" between the numbers in {:?}", line),
+            ParseError::InvalidNumber(number) => write!(f, "invalid number {:?}", number),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Card {
+    /// Our numbers that are also winning numbers
+    fn matches(&self) -> Vec<u32> {
+        self.winning.intersection(&self.ours).copied().collect()
+    }
+}
+
+/// How a card is scored from its number of matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scoring {
+    /// the first match is worth one point, every further match doubles it (part one)
+    Doubling,
+    /// every match is worth one point
+    Linear,
+    /// doubling points, counted once for every copy of the card we hold
+    CopiesWeighted,
+}
+
+impl Scoring {
+    /// Points of a single card with the given number of matches,
+    /// saturating at u64::MAX from 65 doubling matches on
+    fn points(&self, matches: usize) -> u64 {
+        match (self, matches) {
+            (_, 0) => 0,
+            (Scoring::Linear, n) => n as u64,
+            (Scoring::Doubling | Scoring::CopiesWeighted, n) => u32::try_from(n - 1)
+                .ok()
+                .and_then(|exp| 2u64.checked_pow(exp))
+                .unwrap_or(u64::MAX),
+        }
+    }
+
+    /// What a card adds to the total score when we hold `copies` of it, saturating as well
+    fn score(&self, matches: usize, copies: u32) -> u64 {
+        match self {
+            Scoring::CopiesWeighted => self.points(matches).saturating_mul(copies as u64),
+            _ => self.points(matches),
+        }
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(Scoring::Doubling),
+            "linear" => Ok(Scoring::Linear),
+            "copies" => Ok(Scoring::CopiesWeighted),
+            _ => Err(format!("unknown scoring {:?}, expected doubling, linear or copies", s)),
+        }
+    }
+}
+
+impl FromStr for Card {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (card, numbers) = line
+            .split_once(':')
+            .ok_or_else(|| ParseError::MissingId(line.to_string()))?;
+        let id = card
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse::<u32>().ok())
+            .ok_or_else(|| ParseError::MissingId(line.to_string()))?;
+        let (winnums, ournums) = numbers
+            .split_once('|')
+            .ok_or_else(|| ParseError::MissingSeparator(line.to_string()))?;
+        Ok(Card {
+            id,
+            winning: parse_numbers(winnums)?,
+            ours: parse_numbers(ournums)?,
+        })
+    }
+}
+
+fn parse_numbers(numbers: &str) -> Result<HashSet<u32>, ParseError> {
+    numbers
+        .split_ascii_whitespace()
+        .map(|x| x.parse::<u32>().map_err(|_| ParseError::InvalidNumber(x.to_string())))
+        .collect()
+}
+
+/// Parses all cards from the reader in a single pass, skipping empty lines
+fn parse_cards<R: BufRead>(reader: R) -> std::io::Result<Vec<Card>> {
+    let mut cards = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let card = line
+            .parse::<Card>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        cards.push(card);
+    }
+    Ok(cards)
+}
+
+/// What to do when a card wins copies of cards past the end of the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverflowPolicy {
+    /// drop the copies that would go past the last card
+    Clamp,
+    /// refuse to play the table
+    Error,
+    /// continue at the first card again, these copies do not cascade any further
+    /// because the cards they land on have already been played
+    Wrap,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(OverflowPolicy::Clamp),
+            "error" => Ok(OverflowPolicy::Error),
+            "wrap" => Ok(OverflowPolicy::Wrap),
+            _ => Err(format!("unknown overflow policy {:?}, expected clamp, error or wrap", s)),
+        }
+    }
+}
+
+/// Reasons the copy cascade cannot be played
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CascadeError {
+    /// a card wins more copies than there are card ids after it
+    Overflow { card: u32, matches: usize, remaining: usize },
+    /// two cards with the same id
+    DuplicateId(u32),
+    /// a card wins a copy of an id that is not in the table
+    MissingId { card: u32, missing: u32 },
+}
+
+impl fmt::Display for CascadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CascadeError::Overflow { card, matches, remaining } => write!(
+                f,
+                "card {} wins copies of the next {} cards but only {} cards follow",
+                card, matches, remaining
+            ),
+            CascadeError::DuplicateId(id) => write!(f, "card {} appears more than once", id),
+            CascadeError::MissingId { card, missing } => {
+                write!(f, "card {} wins a copy of card {} which does not exist", card, missing)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CascadeError {}
+
+/// Keys the cards by their id so the input need not be sorted
+fn index_cards(cards: Vec<Card>) -> Result<BTreeMap<u32, Card>, CascadeError> {
+    let mut table = BTreeMap::new();
+    for card in cards {
+        let id = card.id;
+        if table.insert(id, card).is_some() {
+            return Err(CascadeError::DuplicateId(id));
+        }
+    }
+    Ok(table)
+}
+
+/// Result of the copy cascade: the copies held per card id
+/// and the ids of the cards whose wins ran past the end of the table
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cascade {
+    played: BTreeMap<u32, u32>,
+    overflowed: Vec<u32>,
+}
+
+/// Part two: card n wins one copy of each of the cards n+1 ..= n+matches,
+/// once for every copy of itself we hold. Wins past the highest id follow the policy,
+/// wins of ids missing in between are an error.
+fn play(table: &BTreeMap<u32, Card>, policy: OverflowPolicy) -> Result<Cascade, CascadeError> {
+    let mut played: BTreeMap<u32, u32> = table.keys().map(|&id| (id, 1)).collect();
+    let mut overflowed = Vec::new();
+    let (Some(&first), Some(&last)) = (table.keys().next(), table.keys().next_back()) else {
+        return Ok(Cascade { played, overflowed });
+    };
+    // ids are visited in ascending order so every card has all its copies before it is played
+    for (&id, card) in table {
+        let matches = card.matches().len();
+        let remaining = (last - id) as usize;
+        if matches > remaining {
+            if policy == OverflowPolicy::Error {
+                return Err(CascadeError::Overflow { card: id, matches, remaining });
+            }
+            overflowed.push(id);
+        }
+        let copies = played[&id];
+        // in u64, ids near u32::MAX or spanning all of u32 would overflow otherwise
+        let (id64, first64, last64) = (id as u64, first as u64, last as u64);
+        for w in 0..matches as u64 {
+            let target = match policy {
+                _ if id64 + w < last64 => id64 + w + 1,
+                OverflowPolicy::Wrap => first64 + (id64 + w + 1 - first64) % (last64 - first64 + 1),
+                _ => break,
+            } as u32;
+            let count = played
+                .get_mut(&target)
+                .ok_or(CascadeError::MissingId { card: id, missing: target })?;
+            *count += copies;
+        }
+    }
+    Ok(Cascade { played, overflowed })
+}
 
 fn main() -> std::io::Result<()> {
     let input_file = "input.txt";
 
-    let mut total_points: u32 = 0;
-    
+    // `--overflow clamp|error|wrap` decides what happens to copies won past the last card,
+    // `--scoring doubling|linear|copies` picks the scoring for the per-card table
+    let mut policy = OverflowPolicy::Clamp;
+    let mut scoring = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let result = match (arg.as_str(), args.next()) {
+            ("--overflow", Some(value)) => value.parse().map(|p| policy = p),
+            ("--scoring", Some(value)) => value.parse().map(|s| scoring = Some(s)),
+            _ => Err(format!("unknown argument {:?}", arg)),
+        };
+        result.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
 
     // Get the current executable path
     let mut exe_path = env::current_exe().expect("Failed to get the executable path");
@@ -20,66 +270,180 @@ fn main() -> std::io::Result<()> {
 
     println!("Input file: {:?}", exe_path);
 
-    // Open file and save line count
-    let mut file = File::open(exe_path).expect("file not found");
-    let mut reader = BufReader::new(&file);
-    let total_lines = reader.lines().count();
-    let mut played = vec![0; total_lines];
-    // rewind file and open again to prevent borrowing issues
-    file.seek(SeekFrom::Start(0))?;
-    reader = BufReader::new(&file);
-
-    // Iterate over lines
-    for (i, line) in reader.lines().enumerate() {
-        let line = line?;
-        played[i] += 1;
-        //println!("{}", line);
-        // Do stuff with the individual lines
-        let game = line.split_once(": ").unwrap().1;
-        //println!("{}", game);
+    let file = File::open(exe_path).expect("file not found");
+    let cards = parse_cards(BufReader::new(file))?;
 
-        let winnums = game.split_once(" | ").unwrap().0;
-        let ournums = game.split_once(" | ").unwrap().1;
-        //println!("Winners: {} Ours: {}", winnums, ournums);    
+    let mut total_points: u64 = 0;
+    for card in &cards {
+        println!("Winners: {:?} Ours: {:?}", card.winning, card.ours);
+        println!("Matches: {:?}", card.matches());
+        total_points = total_points.saturating_add(Scoring::Doubling.points(card.matches().len()));
+    }
 
-        let winset: HashSet<u32> = winnums
-            .split_ascii_whitespace()
-            .map(|x| x.trim().parse::<u32>().unwrap())
-            .collect();
+    // part two: play extra game cards depending on the current cards
+    // note: part2 only cares about the total number of games played,
+    // scoring the copies as well is available as `--scoring copies`
+    let table = index_cards(cards).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let Cascade { played, overflowed } =
+        play(&table, policy).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    for (id, copies) in &played {
+        println!("Played card {} {} times", id, copies);
+    }
+    if !overflowed.is_empty() {
+        println!("Cards winning past the end ({:?}): {:?}", policy, overflowed);
+    }
 
-        let ourset: HashSet<u32> = ournums
-            .split_ascii_whitespace()
-            .map(|x| x.trim().parse::<u32>().unwrap())
-            .collect();
+    if let Some(scoring) = scoring {
+        print_breakdown(&table, &played, scoring);
+    }
 
-        println!("Winners: {:?} Ours: {:?}", winset, ourset);
+    let total_played: u32 = played.values().sum();
+    println!("Total points: {}", total_points);
+    println!("Total played: {:?}", total_played);
 
-        let matches: Vec<u32> = winset
-            .intersection(&ourset)
-            .map(|x| *x)
-            .collect();
+    Ok(())
+}
 
-        println!("Matches: {:?}", matches);
+/// Prints one row per card with its matches, points, copies held
+/// and what it adds to the running total under the given scoring
+fn print_breakdown(table: &BTreeMap<u32, Card>, played: &BTreeMap<u32, u32>, scoring: Scoring) {
+    println!("Scoring: {:?}", scoring);
+    println!("{:>6} {:>8} {:>8} {:>10} {:>14} {:>14}", "Card", "Matches", "Points", "Copies", "Contribution", "Cumulative");
+    let mut cumulative: u64 = 0;
+    for (id, card) in table {
+        let matches = card.matches().len();
+        let copies = played[id];
+        let contribution = scoring.score(matches, copies);
+        cumulative = cumulative.saturating_add(contribution);
+        println!(
+            "{:>6} {:>8} {:>8} {:>10} {:>14} {:>14}",
+            id,
+            matches,
+            scoring.points(matches),
+            copies,
+            contribution,
+            cumulative
+        );
+    }
+    println!("Total score ({:?}): {}", scoring, cumulative);
+}
 
-        if matches.len() > 0 {
-            total_points += u32::pow(2, matches.len() as u32 - 1);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // part two: play extra game cards depending on the current cards
-        // note: should also increase score but apparently part2 only 
-        // cares about total number of games played, so we will leave solution part 1 as is
-        for w in 0..matches.len() {
-            played[i+w+1] += played[i];
-        }
+    /// A card with the given id and `matches` of our numbers winning
+    fn card(id: u32, matches: u32) -> Card {
+        Card { id, winning: (0..matches).collect(), ours: (0..matches).chain(100..103).collect() }
+    }
 
-        println!("Played this card {} times", played[i]);
+    fn make_table(cards: &[(u32, u32)]) -> BTreeMap<u32, Card> {
+        index_cards(cards.iter().map(|&(id, matches)| card(id, matches)).collect()).unwrap()
+    }
 
+    #[test]
+    fn clamp_drops_copies_past_the_last_card() {
+        let table = make_table(&[(1, 0), (2, 2), (3, 1)]);
+        let Cascade { played, overflowed } = play(&table, OverflowPolicy::Clamp).unwrap();
+        assert_eq!(played, BTreeMap::from([(1, 1), (2, 1), (3, 2)]));
+        assert_eq!(overflowed, vec![2, 3]);
     }
 
-    let total_played: u32 = played.iter().sum();
-    println!("Total points: {}", total_points);
-    println!("Total played: {:?}", total_played);
+    #[test]
+    fn clamp_last_card_winning_exactly_nothing_does_not_overflow() {
+        let table = make_table(&[(1, 2), (2, 1), (3, 0)]);
+        let Cascade { played, overflowed } = play(&table, OverflowPolicy::Clamp).unwrap();
+        assert_eq!(played, BTreeMap::from([(1, 1), (2, 2), (3, 4)]));
+        assert!(overflowed.is_empty());
+    }
 
-    Ok(())
-}
+    #[test]
+    fn error_reports_the_first_overflowing_card() {
+        let table = make_table(&[(1, 0), (2, 1), (3, 2)]);
+        assert_eq!(
+            play(&table, OverflowPolicy::Error),
+            Err(CascadeError::Overflow { card: 3, matches: 2, remaining: 0 })
+        );
+        let table = make_table(&[(1, 0), (2, 3), (3, 0)]);
+        assert_eq!(
+            play(&table, OverflowPolicy::Error),
+            Err(CascadeError::Overflow { card: 2, matches: 3, remaining: 1 })
+        );
+    }
+
+    #[test]
+    fn wrap_continues_at_the_first_id() {
+        // ids start at 5, card 7 wins copies of 5 and 6, card 6 of 7 and 5
+        let table = make_table(&[(5, 0), (6, 2), (7, 2)]);
+        let Cascade { played, overflowed } = play(&table, OverflowPolicy::Wrap).unwrap();
+        assert_eq!(played, BTreeMap::from([(5, 1 + 1 + 2), (6, 1 + 2), (7, 2)]));
+        assert_eq!(overflowed, vec![6, 7]);
+    }
 
+    #[test]
+    fn wrap_at_the_end_of_u32() {
+        let table = make_table(&[(u32::MAX - 1, 0), (u32::MAX, 3)]);
+        let Cascade { played, overflowed } = play(&table, OverflowPolicy::Wrap).unwrap();
+        assert_eq!(played, BTreeMap::from([(u32::MAX - 1, 3), (u32::MAX, 2)]));
+        assert_eq!(overflowed, vec![u32::MAX]);
+
+        // ids spanning all of u32: the last card wraps onto the first
+        let table = make_table(&[(0, 0), (u32::MAX, 1)]);
+        let Cascade { played, .. } = play(&table, OverflowPolicy::Wrap).unwrap();
+        assert_eq!(played, BTreeMap::from([(0, 2), (u32::MAX, 1)]));
+        let table = make_table(&[(0, 0), (u32::MAX, 2)]);
+        assert_eq!(
+            play(&table, OverflowPolicy::Wrap),
+            Err(CascadeError::MissingId { card: u32::MAX, missing: 1 })
+        );
+        let table = make_table(&[(0, 0), (u32::MAX, 2)]);
+        assert_eq!(play(&table, OverflowPolicy::Clamp).unwrap().overflowed, vec![u32::MAX]);
+    }
+
+    #[test]
+    fn doubling_points_saturate() {
+        assert_eq!(Scoring::Doubling.points(1), 1);
+        assert_eq!(Scoring::Doubling.points(64), 1 << 63);
+        assert_eq!(Scoring::Doubling.points(65), u64::MAX);
+        assert_eq!(Scoring::CopiesWeighted.score(64, 3), u64::MAX);
+        assert_eq!(Scoring::Linear.points(65), 65);
+    }
+
+    #[test]
+    fn shuffled_input_plays_like_sorted_input() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
+        let sorted = parse_cards(input.as_bytes()).unwrap();
+        let mut shuffled = sorted.clone();
+        shuffled.swap(0, 4);
+        shuffled.swap(1, 5);
+        shuffled.reverse();
+        assert_ne!(shuffled, sorted);
+        for policy in [OverflowPolicy::Clamp, OverflowPolicy::Error, OverflowPolicy::Wrap] {
+            let expected = play(&index_cards(sorted.clone()).unwrap(), policy).unwrap();
+            assert_eq!(play(&index_cards(shuffled.clone()).unwrap(), policy).unwrap(), expected);
+        }
+        let played = play(&index_cards(shuffled).unwrap(), OverflowPolicy::Clamp).unwrap().played;
+        assert_eq!(played.values().sum::<u32>(), 30);
+    }
+
+    #[test]
+    fn duplicate_ids_are_an_error() {
+        let cards = vec![card(1, 1), card(2, 0), card(1, 0)];
+        assert_eq!(index_cards(cards), Err(CascadeError::DuplicateId(1)));
+    }
+
+    #[test]
+    fn missing_id_in_between_is_an_error() {
+        let table = make_table(&[(1, 2), (3, 0)]);
+        assert_eq!(
+            play(&table, OverflowPolicy::Clamp),
+            Err(CascadeError::MissingId { card: 1, missing: 2 })
+        );
+    }
+}
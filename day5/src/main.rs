@@ -1,4 +1,6 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{read_to_string};
+use std::ops::RangeInclusive;
 
 #[derive(Debug)]
 struct RangeMap {
@@ -13,33 +15,51 @@ struct CategoryMaps {
     maps: Vec<RangeMap>,
 }
 
+/// A RangeMap maps the half-open source range [src, src + length) onto [dest, dest + length).
+/// The ends are never computed directly, so maps reaching up to and including u64::MAX
+/// (src + length == 2^64) are fine, only maps going beyond that are invalid.
 impl RangeMap {
     fn map_value(&self, input: u64) -> u64 {
         // if number is not contained in the range, return the input
-        if input < self.src_range_start {
-            return input;
-        }
-        if input >= self.src_range_start + self.range_length {
+        if !self.contains(input) {
             return input;
         }
         let offset = input - self.src_range_start;
-        let dest = self.dest_range_start + offset;
-        dest
+        self.dest_range_start
+            .checked_add(offset)
+            .expect("destination range overflows u64, validate the almanac first")
     }
 
     fn contains(&self, input: u64) -> bool {
-        if input < self.src_range_start {
-            return false;
-        }
-        if input >= self.src_range_start + self.range_length {
-            return false;
-        }
-        true
+        // input - start < length instead of input < start + length, which could overflow
+        input >= self.src_range_start && input - self.src_range_start < self.range_length
     }
-}
 
+    /// The source range as inclusive interval, None for a map of length zero
+    fn src_range(&self) -> Option<RangeInclusive<u64>> {
+        let last = self.src_range_start.checked_add(self.range_length.checked_sub(1)?)?;
+        Some(self.src_range_start..=last)
+    }
+
+    /// True if either range would go beyond u64::MAX
+    fn overflows(&self) -> bool {
+        const END: u128 = u64::MAX as u128 + 1;
+        self.src_range_start as u128 + self.range_length as u128 > END
+            || self.dest_range_start as u128 + self.range_length as u128 > END
+    }
+}
 
 impl CategoryMaps {
+    /// The category this maps from, "seed" for "seed-to-soil"
+    pub fn source(&self) -> Option<&str> {
+        self.category_name.split_once("-to-").map(|(source, _)| source)
+    }
+
+    /// The category this maps to, "soil" for "seed-to-soil"
+    pub fn destination(&self) -> Option<&str> {
+        self.category_name.split_once("-to-").map(|(_, destination)| destination)
+    }
+
     pub fn parse_maps(category_chunk: &str) -> CategoryMaps {
         let mut lines = category_chunk.lines();
 
@@ -77,6 +97,377 @@ impl CategoryMaps {
         //print!(" -> {} {}", self.category_name.split("-").last().unwrap(), current_value);
         current_value
     }
+
+    /// Splits the interval at the boundaries of the RangeMaps into pieces of
+    /// (source interval, mapped start). Each piece goes through the first map containing it,
+    /// the pieces not covered by any map map to themselves.
+    fn split(&self, range: RangeInclusive<u64>) -> Vec<(RangeInclusive<u64>, u64)> {
+        let mut pieces = Vec::new();
+        let mut unmapped = vec![range];
+        for map in &self.maps {
+            let Some(src) = map.src_range() else { continue };
+            let mut remaining = Vec::new();
+            for range in unmapped {
+                let start = *range.start().max(src.start());
+                let end = *range.end().min(src.end());
+                if start > end {
+                    remaining.push(range);
+                    continue;
+                }
+                pieces.push((start..=end, map.map_value(start)));
+                // start > range.start() and end < range.end(), so neither of these overflow
+                if *range.start() < start {
+                    remaining.push(*range.start()..=start - 1);
+                }
+                if end < *range.end() {
+                    remaining.push(end + 1..=*range.end());
+                }
+            }
+            unmapped = remaining;
+        }
+        pieces.extend(unmapped.into_iter().map(|range| {
+            let start = *range.start();
+            (range, start)
+        }));
+        pieces
+    }
+
+    /// Maps whole intervals instead of single values, splitting them with `split`
+    pub fn map_ranges(&self, ranges: &[RangeInclusive<u64>]) -> Vec<RangeInclusive<u64>> {
+        ranges
+            .iter()
+            .flat_map(|range| self.split(range.clone()))
+            .map(|(src, dest)| dest..=dest + (src.end() - src.start()))
+            .collect()
+    }
+
+    /// All source intervals whose values map into one of the given intervals,
+    /// i.e. the inverse of `map_ranges` with the identity passthrough included.
+    /// The result is sorted and adjacent intervals are merged.
+    pub fn preimages(&self, ranges: &[RangeInclusive<u64>]) -> Vec<RangeInclusive<u64>> {
+        let mut preimages = Vec::new();
+        for (src, dest) in self.split(0..=u64::MAX) {
+            let dest_end = dest + (src.end() - src.start());
+            for range in ranges {
+                let start = *range.start().max(&dest);
+                let end = *range.end().min(&dest_end);
+                if start <= end {
+                    preimages.push(src.start() + (start - dest)..=src.start() + (end - dest));
+                }
+            }
+        }
+        merge_ranges(preimages)
+    }
+
+    /// Inverse of `map_value`: every input that maps to the given value
+    pub fn unmap_value(&self, value: u64) -> Vec<RangeInclusive<u64>> {
+        self.preimages(&[value..=value])
+    }
+
+    /// Inverse of mapping through all categories in order,
+    /// e.g. all seeds that end up at the given location
+    pub fn unmap_chain(categories: &[&CategoryMaps], value: u64) -> Vec<RangeInclusive<u64>> {
+        match categories.split_last() {
+            Some((last, rest)) => rest
+                .iter()
+                .rev()
+                .fold(last.unmap_value(value), |ranges, category| category.preimages(&ranges)),
+            None => vec![value..=value],
+        }
+    }
+
+    /// Composes this category with the following one ("seed-to-soil" and "soil-to-fertilizer"
+    /// become "seed-to-fertilizer"). The result consists of sorted, non-overlapping RangeMaps
+    /// so it can be searched by `lookup`. Parts mapping to themselves are left out,
+    /// they pass through like any value not covered by a map.
+    pub fn compose(&self, next: &CategoryMaps) -> CategoryMaps {
+        let mut maps: Vec<RangeMap> = Vec::new();
+        for (src, dest) in self.split(0..=u64::MAX) {
+            for (next_src, next_dest) in next.split(dest..=dest + (src.end() - src.start())) {
+                let src_range_start = src.start() + (next_src.start() - dest);
+                if src_range_start != next_dest {
+                    // not the identity, so the piece is shorter than the whole domain
+                    maps.push(RangeMap {
+                        dest_range_start: next_dest,
+                        src_range_start,
+                        range_length: next_src.end() - next_src.start() + 1,
+                    });
+                }
+            }
+        }
+        maps.sort_by_key(|map| map.src_range_start);
+
+        // merge neighbouring maps that continue each other on both sides
+        let mut merged: Vec<RangeMap> = Vec::with_capacity(maps.len());
+        for map in maps {
+            match merged.last_mut() {
+                Some(last)
+                    if last.src_range_start.checked_add(last.range_length) == Some(map.src_range_start)
+                        && last.dest_range_start.checked_add(last.range_length) == Some(map.dest_range_start) =>
+                {
+                    last.range_length += map.range_length;
+                }
+                _ => merged.push(map),
+            }
+        }
+
+        let from = self.category_name.split("-to-").next().unwrap_or_default();
+        let to = next.category_name.split("-to-").last().unwrap_or_default();
+        CategoryMaps { category_name: format!("{}-to-{}", from, to), maps: merged }
+    }
+
+    /// Composes all categories in order into a single one, e.g. seed-to-location
+    pub fn compose_all(categories: &[&CategoryMaps]) -> Option<CategoryMaps> {
+        let (first, rest) = categories.split_first()?;
+        let identity = CategoryMaps { category_name: first.category_name.clone(), maps: Vec::new() };
+        Some(rest.iter().fold(identity.compose(first), |composed, next| composed.compose(next)))
+    }
+
+    /// Maps a value in O(log n) by binary search,
+    /// only valid for sorted and non-overlapping maps as produced by `compose`
+    pub fn lookup(&self, input: u64) -> u64 {
+        let i = self.maps.partition_point(|map| map.src_range_start <= input);
+        match i.checked_sub(1).map(|i| &self.maps[i]) {
+            Some(map) => map.map_value(input),
+            None => input,
+        }
+    }
+}
+
+/// Sorts the intervals and merges the ones that overlap or touch
+fn merge_ranges(mut ranges: Vec<RangeInclusive<u64>>) -> Vec<RangeInclusive<u64>> {
+    ranges.sort_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                *last = *last.start()..=*last.end().max(range.end());
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+impl std::fmt::Display for CategoryMaps {
+    /// Prints the category in the same format as the almanac
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{} map:", self.category_name)?;
+        for map in &self.maps {
+            writeln!(f, "{} {} {}", map.dest_range_start, map.src_range_start, map.range_length)?;
+        }
+        Ok(())
+    }
+}
+
+/// How RangeMaps with overlapping source ranges within one category are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverlapPolicy {
+    /// the first map in the file containing a value wins (what AoC expects)
+    FirstWins,
+    /// the last map in the file containing a value wins
+    LastWins,
+    /// overlapping maps are an error
+    Reject,
+}
+
+impl std::str::FromStr for OverlapPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(OverlapPolicy::FirstWins),
+            "last" => Ok(OverlapPolicy::LastWins),
+            "reject" => Ok(OverlapPolicy::Reject),
+            _ => Err(format!("unknown overlap policy {:?}, expected first, last or reject", s)),
+        }
+    }
+}
+
+/// Problems found in the RangeMaps of a category, maps are given by their index
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ValidationIssue {
+    /// two maps share part of their source range
+    Overlap { category: String, first: usize, second: usize },
+    /// a map of length zero which never applies
+    ZeroLength { category: String, map: usize },
+    /// the source or destination range goes beyond u64::MAX
+    Overflow { category: String, map: usize },
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationIssue::Overlap { category, first, second } => {
+                write!(f, "{}: source ranges of maps {} and {} overlap", category, first, second)
+            }
+            ValidationIssue::ZeroLength { category, map } => write!(f, "{}: map {} has length zero", category, map),
+            ValidationIssue::Overflow { category, map } => write!(f, "{}: map {} overflows u64", category, map),
+        }
+    }
+}
+
+impl CategoryMaps {
+    /// Reports overlapping source ranges, zero-length maps and maps whose end overflows
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let category = || self.category_name.clone();
+        let mut issues = Vec::new();
+        for (i, map) in self.maps.iter().enumerate() {
+            if map.range_length == 0 {
+                issues.push(ValidationIssue::ZeroLength { category: category(), map: i });
+            }
+            if map.overflows() {
+                issues.push(ValidationIssue::Overflow { category: category(), map: i });
+            }
+        }
+        // zero-length maps are empty and cannot overlap anything
+        let non_empty = || self.maps.iter().enumerate().filter(|(_, map)| map.range_length > 0);
+        for (i, a) in non_empty() {
+            for (j, b) in non_empty().filter(|&(j, _)| j > i) {
+                // compare in u128 so overflowing maps do not hide overlaps
+                let a_end = a.src_range_start as u128 + a.range_length as u128;
+                let b_end = b.src_range_start as u128 + b.range_length as u128;
+                if (a.src_range_start as u128) < b_end && (b.src_range_start as u128) < a_end {
+                    issues.push(ValidationIssue::Overlap { category: category(), first: i, second: j });
+                }
+            }
+        }
+        issues
+    }
+
+    /// Applies the overlap policy so that afterwards the first applicable map is
+    /// the one that should win. Zero-length maps are dropped, overflowing maps are rejected.
+    pub fn resolve(&mut self, policy: OverlapPolicy) -> Result<(), AlmanacError> {
+        for issue in self.validate() {
+            match issue {
+                ValidationIssue::Overflow { .. } => return Err(AlmanacError::Invalid(issue)),
+                ValidationIssue::Overlap { .. } if policy == OverlapPolicy::Reject => {
+                    return Err(AlmanacError::Invalid(issue))
+                }
+                _ => {}
+            }
+        }
+        self.maps.retain(|map| map.range_length > 0);
+        if policy == OverlapPolicy::LastWins {
+            self.maps.reverse();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AlmanacError {
+    /// a category name that is not of the form "X-to-Y"
+    InvalidName(String),
+    /// no chain of maps leads from one category to the other
+    MissingLink { from: String, to: String },
+    /// the maps lead in a circle through these categories
+    Cycle(Vec<String>),
+    /// a category's maps cannot be used under the chosen overlap policy
+    Invalid(ValidationIssue),
+}
+
+impl std::fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AlmanacError::InvalidName(name) => write!(f, "invalid category name {:?}, expected \"X-to-Y\"", name),
+            AlmanacError::MissingLink { from, to } => write!(f, "no maps lead from {} to {}", from, to),
+            AlmanacError::Cycle(categories) => write!(f, "maps form a cycle: {}", categories.join(" -> ")),
+            AlmanacError::Invalid(issue) => write!(f, "invalid almanac: {}", issue),
+        }
+    }
+}
+
+impl std::error::Error for AlmanacError {}
+
+/// All category maps of the almanac, linked by their "X-to-Y" names
+/// so they can appear in any order in the file
+#[derive(Debug)]
+struct Almanac {
+    categories: Vec<CategoryMaps>,
+    /// indices of the categories mapping from each source category
+    links: HashMap<String, Vec<usize>>,
+}
+
+impl Almanac {
+    fn new(mut categories: Vec<CategoryMaps>, policy: OverlapPolicy) -> Result<Almanac, AlmanacError> {
+        let mut links: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, category) in categories.iter_mut().enumerate() {
+            category.resolve(policy)?;
+            let source = category
+                .source()
+                .ok_or_else(|| AlmanacError::InvalidName(category.category_name.clone()))?;
+            links.entry(source.to_string()).or_default().push(i);
+        }
+        let almanac = Almanac { categories, links };
+        almanac.check_cycles()?;
+        Ok(almanac)
+    }
+
+    fn destinations(&self, source: &str) -> impl Iterator<Item = (usize, &str)> {
+        self.links
+            .get(source)
+            .into_iter()
+            .flatten()
+            .filter_map(|&i| Some((i, self.categories[i].destination()?)))
+    }
+
+    /// Depth first search from every category, a category reached again
+    /// while it is still on the current path closes a cycle
+    fn check_cycles(&self) -> Result<(), AlmanacError> {
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut sources: Vec<&String> = self.links.keys().collect();
+        sources.sort();
+        for source in sources {
+            let mut path: Vec<&str> = Vec::new();
+            self.visit(source, &mut path, &mut done)?;
+        }
+        Ok(())
+    }
+
+    fn visit<'a>(&'a self, category: &'a str, path: &mut Vec<&'a str>, done: &mut HashSet<&'a str>) -> Result<(), AlmanacError> {
+        if let Some(start) = path.iter().position(|&c| c == category) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|c| c.to_string()).collect();
+            cycle.push(category.to_string());
+            return Err(AlmanacError::Cycle(cycle));
+        }
+        if done.contains(category) {
+            return Ok(());
+        }
+        path.push(category);
+        for (_, destination) in self.destinations(category) {
+            self.visit(destination, path, done)?;
+        }
+        path.pop();
+        done.insert(category);
+        Ok(())
+    }
+
+    /// The maps to apply in order to convert from one category to another,
+    /// found by breadth first search so the shortest chain wins
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&CategoryMaps>, AlmanacError> {
+        let mut previous: HashMap<&str, (&str, usize)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                let mut maps = Vec::new();
+                let mut current = to;
+                while let Some(&(prev, i)) = previous.get(current) {
+                    maps.push(&self.categories[i]);
+                    current = prev;
+                }
+                maps.reverse();
+                return Ok(maps);
+            }
+            for (i, destination) in self.destinations(category) {
+                if destination != from && !previous.contains_key(destination) {
+                    previous.insert(destination, (category, i));
+                    queue.push_back(destination);
+                }
+            }
+        }
+        Err(AlmanacError::MissingLink { from: from.to_string(), to: to.to_string() })
+    }
 }
 
 fn main() -> std::io::Result<()> {
@@ -108,47 +499,115 @@ fn main() -> std::io::Result<()> {
         .trim() // aligns start to first category name
         .split("\n\n")
         .filter(|x| !x.is_empty())
-        .map(|x| CategoryMaps::parse_maps(x))
+        .map(CategoryMaps::parse_maps)
         .collect();
 
+    // report problems with the maps, how overlaps are resolved
+    // is chosen with `--overlaps first|last|reject`
+    for issue in category_maps.iter().flat_map(CategoryMaps::validate) {
+        println!("Almanac issue: {}", issue);
+    }
+    let policy: OverlapPolicy = match std::env::args().skip_while(|arg| arg != "--overlaps").nth(1) {
+        Some(value) => value
+            .parse()
+            .map_err(|e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        None => OverlapPolicy::FirstWins,
+    };
+
+    // link the maps by their names instead of relying on the file order
+    let almanac = Almanac::new(category_maps, policy)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let category_maps = almanac
+        .path("seed", "location")
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    // compose all categories into one seed-to-location mapping,
+    // so every seed is a single binary search away from its location
+    let composed = CategoryMaps::compose_all(&category_maps).expect("no maps in almanac");
+    // `--print-composed` prints the composed table,
+    // `--seeds-for <location>` lists all seeds ending up at that location and
+    // `--convert <from> <to> <value>` converts a value between any two categories
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print-composed" => print!("{}", composed),
+            "--overlaps" => {
+                // already handled before building the almanac
+                args.next();
+            }
+            "--seeds-for" => {
+                let location: u64 = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "--seeds-for needs a location"))?;
+                println!("Seeds for location {}: {:?}", location, CategoryMaps::unmap_chain(&category_maps, location));
+            }
+            "--convert" => {
+                let (from, to, value) = match (args.next(), args.next(), args.next().and_then(|v| v.parse::<u64>().ok())) {
+                    (Some(from), Some(to), Some(value)) => (from, to, value),
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "--convert needs <from> <to> <value>",
+                        ))
+                    }
+                };
+                let path = almanac
+                    .path(&from, &to)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                let converted = path.iter().fold(value, |value, map| map.map_value(value));
+                println!("{} {} -> {} {}", from, value, to, converted);
+            }
+            _ => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unknown argument {:?}", arg)));
+            }
+        }
+    }
+
     // now we start mapping seeds through the range maps
     let mut mapped_seeds: Vec<u64> = Vec::new();
     for seed in seeds {
-        let mut mapped_seed = seed;
-        //print!("Seed {seed}");
-        for category_map in &category_maps {
-            mapped_seed = category_map.map_value(mapped_seed);
-        }
-        //print!("\n");
+        let mapped_seed = composed.lookup(seed);
+        debug_assert_eq!(mapped_seed, category_maps.iter().fold(seed, |value, map| map.map_value(value)));
         mapped_seeds.push(mapped_seed);
     }
 
     let min_location = mapped_seeds.iter().min().unwrap();
-    let mut min_values = Vec::new();
 
+    // map the seed ranges as whole intervals, so the work depends on
+    // the number of ranges and map boundaries instead of the number of seeds
+    // (start, length) becomes start..=start + length - 1, empty ranges are dropped
+    // and ranges running past u64::MAX are cut off there
+    let seed_intervals: Vec<RangeInclusive<u64>> = seed_ranges
+        .iter()
+        .filter(|&&(_, length)| length > 0)
+        .map(|&(start, length)| start..=start.saturating_add(length - 1))
+        .collect();
+    let mut ranges = seed_intervals.clone();
+    for category_map in &category_maps {
+        ranges = category_map.map_ranges(&ranges);
+        println!("{}: {} intervals", category_map.category_name, ranges.len());
+    }
 
-    // TODO this is horribly slow, i probably overlooked some (obvious?) optimization
-    for (start, length) in seed_ranges {
-        let mut mapped_values = Vec::new();
+    // every seed range may have length 0, which leaves nothing to map
+    let Some(min_location_rangeseed) = ranges.iter().map(|range| *range.start()).min() else {
+        println!("Single Seeds: Minimum Location: {min_location}");
+        println!("Seed Ranges: no seed ranges of non-zero length");
+        return Ok(());
+    };
 
-        //print!("Range({}..{}):", start, start+length);
-    
-        for seed in start..start + length {
-            let mut mapped_seed = seed;
-            //print!("\nSeed {seed} ");
-            for category_map in &category_maps {
-                mapped_seed = category_map.map_value(mapped_seed);
-            }
-            mapped_values.push(mapped_seed);
-        }
+    // walk back from the minimum location to the seeds of the ranges that produce it
+    let seeds_at_min: Vec<RangeInclusive<u64>> = CategoryMaps::unmap_chain(&category_maps, min_location_rangeseed)
+        .into_iter()
+        .flat_map(|seeds| {
+            seed_intervals.iter().filter_map(move |range| {
+                let (lo, hi) = (*seeds.start().max(range.start()), *seeds.end().min(range.end()));
+                (lo <= hi).then_some(lo..=hi)
+            })
+        })
+        .collect();
+    println!("Seeds in ranges for location {}: {:?}", min_location_rangeseed, seeds_at_min);
 
-        //println!(" -> Min {}",*mapped_values.iter().min().unwrap());
-    
-        min_values.push(*mapped_values.iter().min().unwrap());
-    }
-    
-    let min_location_rangeseed = min_values.iter().min().unwrap();
-    
     println!("Single Seeds: Minimum Location: {min_location}");
     println!("Seed Ranges: Minimum location: {:?}",min_location_rangeseed);
 
@@ -156,3 +615,290 @@ fn main() -> std::io::Result<()> {
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(dest: u64, src: u64, length: u64) -> RangeMap {
+        RangeMap { dest_range_start: dest, src_range_start: src, range_length: length }
+    }
+
+    fn category(maps: Vec<RangeMap>) -> CategoryMaps {
+        CategoryMaps { category_name: "a-to-b".to_string(), maps }
+    }
+
+    #[test]
+    fn map_reaching_u64_max_is_allowed() {
+        // src + length == 2^64
+        let mut maps = category(vec![map(0, u64::MAX - 9, 10)]);
+        assert_eq!(maps.validate(), vec![]);
+        assert_eq!(maps.resolve(OverlapPolicy::Reject), Ok(()));
+        let m = &maps.maps[0];
+        assert!(m.contains(u64::MAX));
+        assert_eq!(m.map_value(u64::MAX), 9);
+        assert_eq!(m.map_value(u64::MAX - 9), 0);
+        assert_eq!(m.map_value(u64::MAX - 10), u64::MAX - 10);
+        assert_eq!(m.src_range(), Some(u64::MAX - 9..=u64::MAX));
+        assert!(!m.overflows());
+        // destination reaching u64::MAX as well
+        assert!(!map(u64::MAX - 9, 0, 10).overflows());
+        assert_eq!(map(u64::MAX - 9, 0, 10).map_value(9), u64::MAX);
+    }
+
+    #[test]
+    fn map_beyond_u64_max_is_rejected() {
+        // src + length == 2^64 + 1
+        let overflow = ValidationIssue::Overflow { category: "a-to-b".to_string(), map: 0 };
+        let mut maps = category(vec![map(0, u64::MAX - 9, 11)]);
+        assert!(maps.maps[0].overflows());
+        assert_eq!(maps.validate(), vec![overflow.clone()]);
+        assert_eq!(maps.resolve(OverlapPolicy::FirstWins), Err(AlmanacError::Invalid(overflow.clone())));
+        assert_eq!(maps.maps[0].src_range(), None);
+        // same for the destination
+        let mut maps = category(vec![map(u64::MAX - 9, 0, 11)]);
+        assert_eq!(maps.resolve(OverlapPolicy::LastWins), Err(AlmanacError::Invalid(overflow)));
+    }
+
+    #[test]
+    fn contains_at_the_edges() {
+        assert!(!map(0, 0, 0).contains(0));
+        assert!(map(0, u64::MAX, 1).contains(u64::MAX));
+        assert!(!map(0, u64::MAX - 1, 1).contains(u64::MAX));
+        assert!(map(0, 0, u64::MAX).contains(u64::MAX - 1));
+        assert!(!map(0, 0, u64::MAX).contains(u64::MAX));
+        assert!(!map(0, 5, 3).contains(4));
+        assert!(map(0, 5, 3).contains(7));
+        assert!(!map(0, 5, 3).contains(8));
+    }
+
+    #[test]
+    fn src_range_of_zero_length_map() {
+        assert_eq!(map(0, 0, 0).src_range(), None);
+        assert_eq!(map(0, u64::MAX, 0).src_range(), None);
+        assert_eq!(map(0, 0, 1).src_range(), Some(0..=0));
+        assert!(!map(0, u64::MAX, 0).overflows());
+    }
+
+    #[test]
+    #[should_panic(expected = "destination range overflows u64")]
+    fn map_value_past_u64_max_panics() {
+        // not validated: the destination of the second value is 2^64
+        map(u64::MAX, 0, 2).map_value(1);
+    }
+
+    /// Deterministic pseudo random numbers below `n`, xorshift64 with a fixed seed
+    fn rng() -> impl FnMut(u64) -> u64 {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        move |n| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        }
+    }
+
+    /// A few maps within 0..40, possibly overlapping, out of order and of length 0
+    fn random_category(next: &mut impl FnMut(u64) -> u64, name: &str) -> CategoryMaps {
+        let maps = (0..next(5)).map(|_| map(next(40), next(40), next(12))).collect();
+        CategoryMaps { category_name: name.to_string(), maps }
+    }
+
+    fn random_chain(next: &mut impl FnMut(u64) -> u64) -> Vec<CategoryMaps> {
+        let names = ["a", "b", "c", "d"];
+        (0..1 + next(3) as usize)
+            .map(|i| random_category(next, &format!("{}-to-{}", names[i], names[i + 1])))
+            .collect()
+    }
+
+    fn chain_value(chain: &[CategoryMaps], value: u64) -> u64 {
+        chain.iter().fold(value, |value, category| category.map_value(value))
+    }
+
+    #[test]
+    fn compose_matches_mapping_value_by_value() {
+        let mut next = rng();
+        for _ in 0..500 {
+            let chain = random_chain(&mut next);
+            let refs: Vec<&CategoryMaps> = chain.iter().collect();
+            let composed = CategoryMaps::compose_all(&refs).unwrap();
+            // sorted, non-overlapping and no identity pieces, as lookup needs
+            for pair in composed.maps.windows(2) {
+                assert!(pair[0].src_range_start + pair[0].range_length <= pair[1].src_range_start, "{}", composed);
+            }
+            assert!(composed.maps.iter().all(|map| map.range_length > 0 && map.src_range_start != map.dest_range_start));
+            for value in (0..70).chain([u64::MAX - 1, u64::MAX]) {
+                assert_eq!(composed.lookup(value), chain_value(&chain, value), "{} at {}", composed, value);
+                assert_eq!(composed.map_value(value), chain_value(&chain, value));
+            }
+        }
+    }
+
+    #[test]
+    fn compose_out_of_order_maps() {
+        let soil = category(vec![map(52, 50, 48), map(50, 98, 2)]);
+        let fertilizer = CategoryMaps {
+            category_name: "b-to-c".to_string(),
+            maps: vec![map(39, 0, 15), map(0, 15, 37), map(37, 52, 2)],
+        };
+        let composed = soil.compose(&fertilizer);
+        assert_eq!(composed.category_name, "a-to-c");
+        for (seed, expected) in [(79, 81), (14, 53), (55, 57), (13, 52), (98, 35), (99, 36), (100, 100)] {
+            assert_eq!(composed.lookup(seed), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn unmap_chain_matches_brute_force() {
+        let mut next = rng();
+        for _ in 0..300 {
+            let chain = random_chain(&mut next);
+            let refs: Vec<&CategoryMaps> = chain.iter().collect();
+            for value in 0..60 {
+                let preimages = CategoryMaps::unmap_chain(&refs, value);
+                // sorted and merged
+                for pair in preimages.windows(2) {
+                    assert!(pair[0].end() + 1 < *pair[1].start(), "{:?}", preimages);
+                }
+                // every seed below 100 is found exactly when it maps to the value,
+                // maps stay below 80 so larger seeds pass through unchanged
+                for seed in 0..100 {
+                    let found = preimages.iter().any(|range| range.contains(&seed));
+                    assert_eq!(found, chain_value(&chain, seed) == value, "seed {} value {} {:?}", seed, value, preimages);
+                }
+                assert!(preimages.iter().all(|range| *range.end() < 100));
+            }
+            // values past all maps only come from themselves
+            assert_eq!(CategoryMaps::unmap_chain(&refs, u64::MAX), vec![u64::MAX..=u64::MAX]);
+        }
+    }
+
+    #[test]
+    fn preimages_include_the_identity_passthrough() {
+        let maps = category(vec![map(10, 0, 5)]);
+        // 12 comes from 2 through the map and from 12 itself, 7 only from itself
+        assert_eq!(maps.unmap_value(12), vec![2..=2, 12..=12]);
+        assert_eq!(maps.unmap_value(7), vec![7..=7]);
+        // 0..5 is covered by the map, so nothing maps to 3
+        assert_eq!(maps.unmap_value(3), vec![]);
+        // 0..=4 map to 10..=14 and 5..=20 to themselves, merged into one interval
+        assert_eq!(maps.preimages(&[0..=20]), vec![0..=20]);
+        assert_eq!(maps.preimages(&[0..=4]), vec![]);
+        assert_eq!(CategoryMaps::unmap_chain(&[], 7), vec![7..=7]);
+    }
+
+    fn almanac(chunks: &[&str]) -> Result<Almanac, AlmanacError> {
+        let categories = chunks.iter().map(|chunk| CategoryMaps::parse_maps(chunk)).collect();
+        Almanac::new(categories, OverlapPolicy::FirstWins)
+    }
+
+    fn path_names(almanac: &Almanac, from: &str, to: &str) -> Result<Vec<String>, AlmanacError> {
+        Ok(almanac.path(from, to)?.iter().map(|c| c.category_name.clone()).collect())
+    }
+
+    #[test]
+    fn path_through_out_of_order_categories() {
+        let almanac = almanac(&[
+            "light-to-humidity map:\n50 100 5",
+            "seed-to-soil map:\n10 0 5",
+            "soil-to-light map:\n100 10 5",
+        ])
+        .unwrap();
+        assert_eq!(
+            path_names(&almanac, "seed", "humidity").unwrap(),
+            vec!["seed-to-soil", "soil-to-light", "light-to-humidity"]
+        );
+        assert_eq!(path_names(&almanac, "soil", "light").unwrap(), vec!["soil-to-light"]);
+        assert_eq!(path_names(&almanac, "seed", "seed").unwrap(), Vec::<String>::new());
+        let maps = almanac.path("seed", "humidity").unwrap();
+        let value = |v| maps.iter().fold(v, |v, c| c.map_value(v));
+        // 2 -> 12 -> 102 -> 52, 7 is not covered by any map
+        assert_eq!(value(2), 52);
+        assert_eq!(value(7), 7);
+    }
+
+    #[test]
+    fn shortest_path_wins() {
+        let almanac = almanac(&["a-to-b map:", "b-to-c map:", "c-to-d map:", "a-to-d map:"]).unwrap();
+        assert_eq!(path_names(&almanac, "a", "d").unwrap(), vec!["a-to-d"]);
+        assert_eq!(path_names(&almanac, "b", "d").unwrap(), vec!["b-to-c", "c-to-d"]);
+    }
+
+    #[test]
+    fn missing_link() {
+        let almanac = almanac(&["seed-to-soil map:", "light-to-water map:"]).unwrap();
+        let missing = |from: &str, to: &str| AlmanacError::MissingLink { from: from.to_string(), to: to.to_string() };
+        assert_eq!(path_names(&almanac, "seed", "water"), Err(missing("seed", "water")));
+        // links only go one way
+        assert_eq!(path_names(&almanac, "soil", "seed"), Err(missing("soil", "seed")));
+        assert_eq!(path_names(&almanac, "seed", "moon"), Err(missing("seed", "moon")));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let cycle = |names: &[&str]| Err(AlmanacError::Cycle(names.iter().map(|n| n.to_string()).collect()));
+        assert_eq!(almanac(&["a-to-b map:", "b-to-a map:"]).map(|_| ()), cycle(&["a", "b", "a"]));
+        assert_eq!(
+            almanac(&["c-to-a map:", "x-to-a map:", "a-to-b map:", "b-to-c map:"]).map(|_| ()),
+            cycle(&["a", "b", "c", "a"])
+        );
+        assert_eq!(almanac(&["a-to-a map:"]).map(|_| ()), cycle(&["a", "a"]));
+        // two routes to the same category are not a cycle
+        assert!(almanac(&["a-to-b map:", "a-to-c map:", "b-to-d map:", "c-to-d map:"]).is_ok());
+    }
+
+    #[test]
+    fn invalid_category_name() {
+        assert_eq!(
+            almanac(&["seed-to-soil map:", "seeds to soil map:"]).map(|_| ()),
+            Err(AlmanacError::InvalidName("seeds to soil".to_string()))
+        );
+    }
+
+    #[test]
+    fn validate_reports_overlaps_by_index() {
+        // 0..10, 20..30, 5..25 (overlaps both), 30..40 (touches the second), empty at 7
+        let maps = category(vec![map(100, 0, 10), map(200, 20, 10), map(300, 5, 20), map(400, 30, 10), map(500, 7, 0)]);
+        let overlap = |first, second| ValidationIssue::Overlap { category: "a-to-b".to_string(), first, second };
+        assert_eq!(
+            maps.validate(),
+            vec![ValidationIssue::ZeroLength { category: "a-to-b".to_string(), map: 4 }, overlap(0, 2), overlap(1, 2)]
+        );
+        assert_eq!(category(vec![map(0, 0, 5), map(0, 5, 5)]).validate(), vec![]);
+    }
+
+    #[test]
+    fn resolve_applies_the_overlap_policy() {
+        // 0..10 -> 100..110 and 5..15 -> 200..210 share 5..10
+        let overlapping = || category(vec![map(100, 0, 10), map(200, 5, 10)]);
+
+        let mut first = overlapping();
+        first.resolve(OverlapPolicy::FirstWins).unwrap();
+        assert_eq!(first.map_value(7), 107);
+        assert_eq!(first.map_value(12), 207);
+
+        let mut last = overlapping();
+        last.resolve(OverlapPolicy::LastWins).unwrap();
+        assert_eq!(last.map_value(7), 202);
+        assert_eq!(last.map_value(2), 102);
+        assert_eq!(last.map_value(12), 207);
+
+        let mut reject = overlapping();
+        assert_eq!(
+            reject.resolve(OverlapPolicy::Reject),
+            Err(AlmanacError::Invalid(ValidationIssue::Overlap { category: "a-to-b".to_string(), first: 0, second: 1 }))
+        );
+    }
+
+    #[test]
+    fn resolve_drops_zero_length_maps() {
+        for policy in [OverlapPolicy::FirstWins, OverlapPolicy::LastWins, OverlapPolicy::Reject] {
+            let mut maps = category(vec![map(100, 0, 10), map(500, 3, 0), map(200, 20, 10)]);
+            assert_eq!(maps.resolve(policy), Ok(()));
+            assert!(maps.maps.iter().all(|m| m.range_length > 0));
+            assert_eq!(maps.maps.len(), 2);
+            assert_eq!(maps.map_value(3), 103);
+            assert_eq!(maps.validate(), vec![]);
+        }
+    }
+}
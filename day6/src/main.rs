@@ -1,81 +1,474 @@
+use std::fmt;
 use std::fs::read_to_string;
 
-const BOAT_BASE_SPEED: u64 = 0; // mm/ms
+/// A race: the time available and the distance record to beat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Race {
+    time: u128,
+    record: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    /// the sheet needs a "Time:" and a "Distance:" line
+    MissingLine(&'static str),
+    /// not a number or too large for a u128
+    InvalidNumber(String),
+    /// different number of times and distances
+    MismatchedColumns { times: usize, distances: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingLine(label) => write!(f, "missing {:?} line", label),
+            ParseError::InvalidNumber(number) => write!(f, "invalid number {:?}", number),
+            ParseError::MismatchedColumns { times, distances } => {
+                write!(f, "{} times but {} distances", times, distances)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Unsigned 256 bit integer as high and low half, just enough to hold time²
+/// of a u128 race without losing precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct U256 {
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    fn from_u128(value: u128) -> U256 {
+        U256 { hi: 0, lo: value }
+    }
+
+    /// Full product of two u128, computed on 64 bit limbs
+    fn mul(a: u128, b: u128) -> U256 {
+        const MASK: u128 = u64::MAX as u128;
+        let (a0, a1, b0, b1) = (a & MASK, a >> 64, b & MASK, b >> 64);
+        let (p00, p01, p10, p11) = (a0 * b0, a0 * b1, a1 * b0, a1 * b1);
+        let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+        U256 {
+            hi: p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64),
+            lo: (p00 & MASK) | (mid << 64),
+        }
+    }
+
+    fn checked_sub(self, other: U256) -> Option<U256> {
+        let (lo, borrow) = self.lo.overflowing_sub(other.lo);
+        let hi = self.hi.checked_sub(other.hi)?.checked_sub(borrow as u128)?;
+        Some(U256 { hi, lo })
+    }
+
+    /// Quotient and remainder of the division by a u64, long division on 64 bit limbs
+    fn div_rem(self, divisor: u64) -> (U256, u64) {
+        let divisor = divisor as u128;
+        let mut remainder: u128 = 0;
+        let mut limbs = [self.hi >> 64, self.hi & u64::MAX as u128, self.lo >> 64, self.lo & u64::MAX as u128];
+        for limb in limbs.iter_mut() {
+            let current = (remainder << 64) | *limb;
+            *limb = current / divisor;
+            remainder = current % divisor;
+        }
+        let quotient = U256 { hi: (limbs[0] << 64) | limbs[1], lo: (limbs[2] << 64) | limbs[3] };
+        (quotient, remainder as u64)
+    }
+
+    /// Largest x <= bound with x² <= self, found by bisection
+    fn isqrt(self, bound: u128) -> u128 {
+        let (mut low, mut high) = (0, bound);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if U256::mul(mid, mid) <= self {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        low
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.hi == 0 {
+            return write!(f, "{}", self.lo);
+        }
+        // split into chunks of 19 decimal digits, the most that fit into a u64
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        let mut chunks = Vec::new();
+        let mut rest = *self;
+        while rest > U256::from_u128(0) {
+            let (quotient, remainder) = rest.div_rem(CHUNK);
+            chunks.push(remainder);
+            rest = quotient;
+        }
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap_or(0))?;
+        for chunk in chunks {
+            write!(f, "{:019}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the boat accelerates: every millisecond the button is held adds `acceleration` mm/ms
+/// to the `base_speed`, up to the optional `max_speed`. The puzzle's boat starts at 0 mm/ms
+/// and accelerates by 1 mm/ms per ms without limit. Speeds saturate at u128::MAX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BoatModel {
+    base_speed: u128,
+    acceleration: u128,
+    max_speed: Option<u128>,
+}
+
+impl Default for BoatModel {
+    fn default() -> Self {
+        BoatModel { base_speed: 0, acceleration: 1, max_speed: None }
+    }
+}
+
+impl BoatModel {
+    fn speed(&self, hold: u128) -> u128 {
+        let speed = self.acceleration.saturating_mul(hold).saturating_add(self.base_speed);
+        self.max_speed.map_or(speed, |max| speed.min(max))
+    }
+
+    /// Holding the button for `hold` ms leaves `time - hold` ms to travel at the reached speed
+    fn distance(&self, race: &Race, hold: u128) -> U256 {
+        U256::mul(self.speed(hold), race.time - hold)
+    }
+
+    fn beats_record(&self, race: &Race, hold: u128) -> bool {
+        hold <= race.time && self.distance(race, hold) > U256::from_u128(race.record)
+    }
+
+    /// The hold time reaching the furthest distance. The distance rises along the parabola
+    /// (base + acc * hold) * (time - hold) up to its vertex (acc * time - base) / (2 * acc)
+    /// or until the speed is capped, and only falls after that.
+    fn optimal_hold(&self, race: &Race) -> u128 {
+        if self.acceleration == 0 {
+            return 0;
+        }
+        let vertex = race.time.saturating_mul(self.acceleration).saturating_sub(self.base_speed)
+            / self.acceleration.saturating_mul(2);
+        let mut candidates = vec![vertex, vertex.saturating_add(1)];
+        if let Some(max) = self.max_speed {
+            // first hold time at the capped speed
+            let capped = max.saturating_sub(self.base_speed).div_ceil(self.acceleration);
+            candidates.extend([capped.saturating_sub(1), capped]);
+        }
+        candidates
+            .into_iter()
+            .map(|hold| hold.min(race.time))
+            .max_by_key(|&hold| (self.distance(race, hold), std::cmp::Reverse(hold)))
+            .unwrap_or(0)
+    }
+
+    /// Smallest and largest hold time beating the record, if any.
+    /// Without a speed cap getting in the way this is solved in closed form, otherwise the
+    /// boundaries are found by bisection on both sides of the optimal hold time.
+    fn winning_range(&self, race: &Race) -> Option<(u128, u128)> {
+        let best = self.optimal_hold(race);
+        if !self.beats_record(race, best) {
+            return None;
+        }
+        let (mut min_hold, mut max_hold) = match self.roots(race) {
+            Some(roots) => roots,
+            None => (
+                first_true(0, best, |h| self.beats_record(race, h)),
+                first_true(best, race.time, |h| h == race.time || !self.beats_record(race, h + 1)),
+            ),
+        };
+        // the closed form estimates are at most one off,
+        // nudge them onto the first/last winning hold time
+        while min_hold > 0 && self.beats_record(race, min_hold - 1) {
+            min_hold -= 1;
+        }
+        while !self.beats_record(race, min_hold) {
+            min_hold += 1;
+        }
+        while max_hold < race.time && self.beats_record(race, max_hold + 1) {
+            max_hold += 1;
+        }
+        while !self.beats_record(race, max_hold) {
+            max_hold -= 1;
+        }
+        Some((min_hold, max_hold))
+    }
+
+    /// Estimates of the roots of (base + acc * h) * (time - h) = record, i.e.
+    /// (acc * time - base ± sqrt((acc * time + base)² - 4 * acc * record)) / (2 * acc),
+    /// computed with an exact integer square root in 256 bits. None when the speed cap
+    /// is reached within the race or the numbers get too large for the closed form.
+    fn roots(&self, race: &Race) -> Option<(u128, u128)> {
+        let (acc, base) = (self.acceleration, self.base_speed);
+        if acc == 0 {
+            return None;
+        }
+        let top_speed = acc.checked_mul(race.time)?.checked_add(base)?;
+        if self.max_speed.is_some_and(|max| max < top_speed) {
+            return None;
+        }
+        let acc_record = U256::mul(acc, race.record);
+        let four_acc_record = U256 { hi: acc_record.hi.checked_mul(4)? | (acc_record.lo >> 126), lo: acc_record.lo << 2 };
+        let discriminant = U256::mul(top_speed, top_speed).checked_sub(four_acc_record)?;
+        let root = discriminant.isqrt(top_speed);
+        // acc * time - base may be negative, roots below zero are clamped to 0
+        let acc_time = acc * race.time;
+        let (low, high) = if acc_time >= base {
+            let b = acc_time - base;
+            (b.saturating_sub(root), b.checked_add(root)?)
+        } else {
+            (0, root.saturating_sub(base - acc_time))
+        };
+        let two_acc = acc.checked_mul(2)?;
+        Some(((low / two_acc).min(race.time), (high / two_acc).min(race.time)))
+    }
+
+    fn solve(&self, race: &Race) -> RaceSolution {
+        let optimal_hold = self.optimal_hold(race);
+        RaceSolution {
+            winning: self.winning_range(race),
+            optimal_hold,
+            max_distance: self.distance(race, optimal_hold),
+        }
+    }
+}
+
+/// The hold times beating the record (min_hold, max_hold) if any,
+/// and the hold time reaching the furthest distance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RaceSolution {
+    winning: Option<(u128, u128)>,
+    optimal_hold: u128,
+    max_distance: U256,
+}
+
+impl RaceSolution {
+    fn winning_strategies(&self) -> u128 {
+        self.winning.map_or(0, |(min_hold, max_hold)| max_hold - min_hold + 1)
+    }
+}
+
+/// Smallest x in low..=high for which the predicate holds, assuming it is monotone
+/// (false up to some point, true afterwards) and holds for `high`
+fn first_true(mut low: u128, mut high: u128, predicate: impl Fn(u128) -> bool) -> u128 {
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if predicate(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    low
+}
+
+/// Parses the numbers following the label, e.g. "Time:      7  15   30"
+/// as well as all of its digits concatenated into a single number (part two)
+fn parse_line(line: Option<&str>, label: &'static str) -> Result<(Vec<u128>, u128), ParseError> {
+    let numbers = line
+        .and_then(|line| line.trim().strip_prefix(label))
+        .ok_or(ParseError::MissingLine(label))?;
+    let columns = numbers
+        .split_whitespace()
+        .map(|x| x.parse().map_err(|_| ParseError::InvalidNumber(x.to_string())))
+        .collect::<Result<Vec<u128>, ParseError>>()?;
+    let digits: String = numbers.chars().filter(|c| !c.is_whitespace()).collect();
+    let single = digits.parse().map_err(|_| ParseError::InvalidNumber(digits))?;
+    Ok((columns, single))
+}
+
+/// The separate races of part one and the single concatenated race of part two
+fn parse_sheet(sheet: &str) -> Result<(Vec<Race>, Race), ParseError> {
+    let mut lines = sheet.lines().filter(|line| !line.trim().is_empty());
+    let (times, single_time) = parse_line(lines.next(), "Time:")?;
+    let (distances, single_distance) = parse_line(lines.next(), "Distance:")?;
+    if times.len() != distances.len() {
+        return Err(ParseError::MismatchedColumns { times: times.len(), distances: distances.len() });
+    }
+    let races = times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, record)| Race { time, record })
+        .collect();
+    Ok((races, Race { time: single_time, record: single_distance }))
+}
 
 fn main() -> std::io::Result<()> {
     let input_file = "sample.txt";
 
+    // the boat can be changed with `--base-speed <mm/ms>`, `--acceleration <mm/ms per ms>`
+    // and `--max-speed <mm/ms>`, by default it is the puzzle's boat
+    let mut model = BoatModel::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().and_then(|value| value.parse::<u128>().ok());
+        match (arg.as_str(), value) {
+            ("--base-speed", Some(value)) => model.base_speed = value,
+            ("--acceleration", Some(value)) => model.acceleration = value,
+            ("--max-speed", Some(value)) => model.max_speed = Some(value),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid argument {:?}, expected --base-speed, --acceleration or --max-speed with a number", arg),
+                ))
+            }
+        }
+    }
+
     println!("Input file: {:?}", input_file);
+    println!("Boat: {:?}", model);
 
     // read file line by line
     let file_str = read_to_string(input_file)?;
 
-    let (times_str, distances_str) = file_str.split_once("\n").unwrap();
-
-    let (mut times, mut dist_records): (Vec<u64>, Vec<u64>) = (
-        times_str.split_whitespace().filter_map(|x| x.parse().ok()).collect(),
-        distances_str.split_whitespace().filter_map(|x| x.parse().ok()).collect()
-    );
+    // the additional race where all input digits are concatenated
+    // is just appended to the end of the list
+    let (mut races, single_race) =
+        parse_sheet(&file_str).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    races.push(single_race);
 
     println!("Course records:");
-    for (time, distance) in times.iter().zip(dist_records.iter()) {
-        println!("{} millimeter in {} milliseconds", time, distance);
+    for race in &races {
+        println!("{} millimeter in {} milliseconds", race.record, race.time);
     }
 
-    // create an additional pair where all input digits are concatenated
-    let (single_times, single_dist_records): (u64, u64) = (
-        times_str.chars().filter(|c| c.is_digit(10)).collect::<String>().parse().unwrap(),
-        distances_str.chars().filter(|c| c.is_digit(10)).collect::<String>().parse().unwrap()
-    );
-
-    // and just append it to the end of the lists
-    times.push(single_times);
-    dist_records.push(single_dist_records);
-
     // note: we will calculate solutions for part 1 and 1 seperately
 
 
-    // button press for boat increases speed by 1 mm/ms per 1 ms button pressed
-    // boat starts at 0 mm/ms
-    // calculate all possible speeds for each time, distance combination
-    let number_of_winning_strategies = times.iter().zip(dist_records.iter())
-    .map(|(&time, &course_record)| {
-        (BOAT_BASE_SPEED..time).filter_map(move |button_hold_time| {
-            let speed = button_hold_time; // Speed increases by 1 unit for each second the button is held
-            let remaining_time = time - button_hold_time; // Remaining time after holding the button
-            let distance = speed * remaining_time; // Calculate distance
+    // button press for boat increases speed by the model's acceleration per 1 ms button pressed
+    // boat starts at the model's base speed
+    // solve for the range of winning button hold times for each time, distance combination
+    let solutions = races.iter().map(|race| model.solve(race)).collect::<Vec<_>>();
+    let number_of_winning_strategies = solutions
+        .iter()
+        .map(RaceSolution::winning_strategies)
+        .collect::<Vec<_>>();
 
-            if distance > course_record {
-                Some(button_hold_time)
-            } else {
-                None
-            }
-        }).count()
-    })
-    .collect::<Vec<_>>();
-
-    for((time, record), strats) in times.iter().zip(dist_records.iter()).zip(number_of_winning_strategies.iter()) {
-        println!("Time {}, Record: {}, Strategies: {}", time, record, strats);
+    for (race, solution) in races.iter().zip(solutions.iter()) {
+        let winning = match solution.winning {
+            Some((min_hold, max_hold)) => format!("{}..={}", min_hold, max_hold),
+            None => "none".to_string(),
+        };
+        println!(
+            "Time {}, Record: {}, Strategies: {}, Winning holds: {}, Optimal hold: {}, Max distance: {}",
+            race.time,
+            race.record,
+            solution.winning_strategies(),
+            winning,
+            solution.optimal_hold,
+            solution.max_distance
+        );
     }
 
-    println!("Part 1: Total ways to win: {}", 
-            number_of_winning_strategies
-            .iter()
-            .take(number_of_winning_strategies.len()-1)
-            .product::<usize>()
-    );
+    // the product of many large counts can exceed even a u128
+    match number_of_winning_strategies
+        .iter()
+        .take(number_of_winning_strategies.len() - 1)
+        .try_fold(1u128, |product, &strats| product.checked_mul(strats))
+    {
+        Some(product) => println!("Part 1: Total ways to win: {}", product),
+        None => println!("Part 1: Total ways to win: more than {}", u128::MAX),
+    }
 
     // Since there is only one entry, we already have the total product of products
-    println!("Part 2: Total ways to win: {:?}", 
+    println!("Part 2: Total ways to win: {:?}",
             number_of_winning_strategies
             .iter()
             .last()
             .unwrap()
-);
+    );
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// First and last winning hold time by trying every hold time
+    fn brute_force(model: &BoatModel, race: &Race) -> Option<(u128, u128)> {
+        let mut winning = (0..=race.time).filter(|&hold| model.beats_record(race, hold));
+        let first = winning.next()?;
+        Some((first, winning.next_back().unwrap_or(first)))
+    }
+
+    #[test]
+    fn isqrt_of_squares_and_neighbours() {
+        for n in [1u128, 2, 3, 10, 1 << 32, u64::MAX as u128, 1 << 100, u128::MAX] {
+            let square = U256::mul(n, n);
+            assert_eq!(square.isqrt(u128::MAX), n);
+            assert_eq!(square.checked_sub(U256::from_u128(1)).unwrap().isqrt(u128::MAX), n - 1);
+        }
+        assert_eq!(U256::from_u128(0).isqrt(u128::MAX), 0);
+        assert_eq!(U256::from_u128(101).isqrt(u128::MAX), 10);
+        // the bound caps the result
+        assert_eq!(U256::from_u128(100).isqrt(7), 7);
+    }
+
+    #[test]
+    fn exact_square_discriminant_excludes_ties() {
+        // (30 - h) * h = 200 for h = 10 and h = 20, which only tie the record
+        let race = Race { time: 30, record: 200 };
+        let model = BoatModel::default();
+        assert_eq!(model.roots(&race), Some((10, 20)));
+        let solution = model.solve(&race);
+        assert_eq!(solution.winning, Some((11, 19)));
+        assert_eq!(solution.winning_strategies(), 9);
+        assert_eq!(solution.optimal_hold, 15);
+        assert_eq!(solution.max_distance, U256::from_u128(225));
+    }
 
+    #[test]
+    fn record_only_tied_at_the_vertex() {
+        // the best distance is exactly the record, nothing wins
+        let race = Race { time: 30, record: 225 };
+        assert_eq!(BoatModel::default().winning_range(&race), None);
+    }
 
+    #[test]
+    fn record_zero_and_time_zero() {
+        let model = BoatModel::default();
+        assert_eq!(model.winning_range(&Race { time: 7, record: 0 }), Some((1, 6)));
+        assert_eq!(model.winning_range(&Race { time: 0, record: 0 }), None);
+        assert_eq!(model.winning_range(&Race { time: 0, record: 5 }), None);
+        assert_eq!(model.solve(&Race { time: 0, record: 0 }).optimal_hold, 0);
+        // a boat that moves without holding wins from hold 0 on
+        let rolling = BoatModel { base_speed: 2, ..BoatModel::default() };
+        assert_eq!(rolling.winning_range(&Race { time: 7, record: 0 }), Some((0, 6)));
+    }
 
+    #[test]
+    fn matches_brute_force_on_small_races() {
+        let mut models = vec![BoatModel::default()];
+        for base_speed in 0..4 {
+            for acceleration in 0..4 {
+                for max_speed in [None, Some(0), Some(3), Some(7)] {
+                    models.push(BoatModel { base_speed, acceleration, max_speed });
+                }
+            }
+        }
+        for model in &models {
+            for time in 0..25 {
+                for record in 0..120 {
+                    let race = Race { time, record };
+                    assert_eq!(model.winning_range(&race), brute_force(model, &race), "{:?} {:?}", model, race);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let (races, single) = parse_sheet("Time:      7  15   30\nDistance:  9  40  200\n").unwrap();
+        let model = BoatModel::default();
+        let product: u128 = races.iter().map(|race| model.solve(race).winning_strategies()).product();
+        assert_eq!(product, 288);
+        assert_eq!(model.solve(&single).winning_strategies(), 71503);
+    }
+}
@@ -1,195 +1,334 @@
+use std::fmt;
 use std::fs::read_to_string;
 
+/// A card as it appears in the input together with its strength under the ruleset
+/// the hand was parsed with. Ordered by strength first.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[repr(u8)]
-// enum Card {
-//     Value(u8),
-//     T = 10,
-//     J = 11,
-//     Q = 12,
-//     K = 13,
-//     A = 14,
-// } // part 1
-enum Card {
-    J = 1,
-    Value(u8), // 2..9
-    T = 10,
-    Q = 11,
-    K = 12,
-    A = 13,
-} // part 2
-
-// implement from trait for card rank
-impl From<char> for Card {
-    fn from(c: char) -> Self {
-        match c {
-            '1'..='9' => Card::Value(c.to_digit(10).unwrap() as u8),
-            'T' => Card::T,
-            'J' => Card::J,
-            'Q' => Card::Q,
-            'K' => Card::K,
-            'A' => Card::A,
-            _ => panic!("Invalid card character"),
-        }
-    }
+struct Card {
+    rank: u8,
+    label: char,
 }
 
 // implement fmt trait
-impl std::fmt::Display for Card {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Card::Value(value) => write!(f, "{}", value),
-            Card::T => write!(f, "T"),
-            Card::J => write!(f, "J"),
-            Card::Q => write!(f, "Q"),
-            Card::K => write!(f, "K"),
-            Card::A => write!(f, "A"),
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+/// Decides how strong each card is, which card, if any, is a joker
+/// and how many cards make a hand and how their count patterns rank.
+/// Part one plays the standard rules, part two lets the J be a weak joker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ruleset {
+    name: String,
+    /// card labels from weakest to strongest
+    order: Vec<char>,
+    joker: Option<char>,
+    hand_size: usize,
+    /// custom count signatures from weakest to strongest. A hand whose best signature
+    /// is missing here is an UnrankedSignature error, which fails the whole run.
+    /// None ranks all signatures by comparing them element by element, which gives
+    /// the usual order, for five cards: high card, one pair, two pair, three of a kind,
+    /// full house, four and five of a kind
+    categories: Option<Vec<Vec<u8>>>,
+}
+
+impl Ruleset {
+    fn standard() -> Ruleset {
+        Ruleset::custom("standard", "23456789TJQKA", None)
+    }
+
+    fn jokers_wild() -> Ruleset {
+        Ruleset::custom("jokers wild", "J23456789TQKA", Some('J'))
+    }
+
+    fn custom(name: &str, order: &str, joker: Option<char>) -> Ruleset {
+        Ruleset {
+            name: name.to_string(),
+            order: order.chars().collect(),
+            joker,
+            hand_size: 5,
+            categories: None,
+        }
+    }
+
+    fn card(&self, label: char) -> Result<Card, ParseError> {
+        self.order
+            .iter()
+            .position(|&c| c == label)
+            .map(|rank| Card { rank: rank as u8, label })
+            .ok_or(ParseError::InvalidCard(label))
+    }
+
+    fn is_joker(&self, card: &Card) -> bool {
+        self.joker == Some(card.label)
+    }
+
+    fn category(&self, signature: &[u8]) -> Result<Category, ParseError> {
+        match &self.categories {
+            None => Ok(Category::Signature(signature.to_vec())),
+            Some(categories) => categories
+                .iter()
+                .position(|c| c == signature)
+                .map(Category::Custom)
+                .ok_or_else(|| ParseError::UnrankedSignature(signature.to_vec())),
         }
     }
+
+    /// All cards a joker can stand in for
+    fn substitutes(&self) -> Vec<Card> {
+        self.order
+            .iter()
+            .filter(|&&c| Some(c) != self.joker)
+            .map(|&c| self.card(c).unwrap())
+            .collect()
+    }
 }
 
-// A Hand of five cards
-// First data field is biggest tuple, second is second highest etc.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-enum HandOfFive {
-    HighCard(Vec<Card>),
-    OnePair(Vec<Card>),
-    TwoPair(Vec<Card>),
-    ThreeOfAKind(Vec<Card>),
-    FullHouse(Vec<Card>),
-    FourOfAKind(Vec<Card>),
-    FiveOfAKind(Vec<Card>),
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    InvalidCard(char),
+    InvalidLine(String),
+    WrongHandSize { expected: usize, found: usize },
+    UnrankedSignature(Vec<u8>),
 }
 
-impl HandOfFive {
-    fn cards(&self) -> &Vec<Card> {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            HandOfFive::HighCard(cards) => cards,
-            HandOfFive::OnePair(cards) => cards,
-            HandOfFive::TwoPair(cards) => cards,
-            HandOfFive::ThreeOfAKind(cards) => cards,
-            HandOfFive::FullHouse(cards) => cards,
-            HandOfFive::FourOfAKind(cards) => cards,
-            HandOfFive::FiveOfAKind(cards) => cards,
+            ParseError::InvalidCard(c) => write!(f, "invalid card {:?} for this ruleset", c),
+            ParseError::InvalidLine(line) => write!(f, "invalid line {:?}, expected \"<cards> <bid>\"", line),
+            ParseError::WrongHandSize { expected, found } => {
+                write!(f, "hand of {} cards, this ruleset plays {}", found, expected)
+            }
+            ParseError::UnrankedSignature(signature) => {
+                write!(f, "card counts {:?} are not a category of this ruleset", signature)
+            }
         }
     }
+}
 
-    fn apply_jokers(&self) -> HandOfFive {
-        let hand_cards = self.cards();
-        // vec of all cards except joker
-        let variants: Vec<Card> = vec![
-            Card::Value(2),
-            Card::Value(3),
-            Card::Value(4),
-            Card::Value(5),
-            Card::Value(6),
-            Card::Value(7),
-            Card::Value(8),
-            Card::Value(9),
-            Card::T,
-            Card::Q,
-            Card::K,
-            Card::A,
-        ];
-        let possible_hands: Vec<HandOfFive> = replace_jokers(hand_cards.clone(), variants, 0)
-            .into_iter()
-            .map(HandOfFive::from)
-            .collect();
-        if possible_hands.is_empty() {
-            return self.clone();
-        } else {
-            possible_hands.iter().max().unwrap().clone()
-        }
+impl std::error::Error for ParseError {}
+
+/// How strong the count signature of a hand is. All hands of one ruleset
+/// use the same variant, so the derived ordering only compares within it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Category {
+    /// default ranking, the signatures themselves compare element by element
+    Signature(Vec<u8>),
+    /// position among the ruleset's custom categories, 0 being the weakest
+    Custom(usize),
+}
+
+/// Name of a count signature, the five card categories have their usual names
+fn category_name(signature: &[u8]) -> String {
+    match signature {
+        [1, 1, 1, 1, 1] => "high card".to_string(),
+        [2, 1, 1, 1] => "one pair".to_string(),
+        [2, 2, 1] => "two pair".to_string(),
+        [3, 1, 1] => "three of a kind".to_string(),
+        [3, 2] => "full house".to_string(),
+        [4, 1] => "four of a kind".to_string(),
+        [5] => "five of a kind".to_string(),
+        _ => signature.iter().map(|c| c.to_string()).collect::<Vec<_>>().join("-"),
     }
 }
 
+/// Parses count signatures like "1,1,1;2,1;3", weakest first.
+/// Every signature must account for all cards of a hand.
+fn parse_categories(s: &str, hand_size: usize) -> Result<Vec<Vec<u8>>, String> {
+    s.split(';')
+        .map(|category| {
+            let mut signature = category
+                .split(',')
+                .map(|count| match count.trim().parse::<u8>() {
+                    Ok(count) if count > 0 => Ok(count),
+                    _ => Err(format!("invalid count {:?} in category {:?}", count, category)),
+                })
+                .collect::<Result<Vec<u8>, String>>()?;
+            signature.sort();
+            signature.reverse();
+            match signature.iter().map(|&c| c as usize).sum::<usize>() {
+                total if total == hand_size => Ok(signature),
+                total => Err(format!("category {:?} has {} cards, hands have {}", category, total, hand_size)),
+            }
+        })
+        .collect()
+}
 
-impl From<Vec<char>> for HandOfFive {
-    fn from(cards: Vec<char>) -> Self {
-        let cards: Vec<Card> = cards.into_iter().map(|c| Card::from(c)).collect();
-        Self::from(cards)
+/// Checks a card order given with `--order`, every label may appear only once
+/// and the rank of each card has to fit in a u8
+fn check_order(order: &str) -> Result<(), String> {
+    let mut seen = std::collections::HashSet::new();
+    if let Some(label) = order.chars().find(|&c| !seen.insert(c)) {
+        return Err(format!("card {:?} appears more than once in --order {:?}", label, order));
+    }
+    match seen.len() {
+        n if n > u8::MAX as usize + 1 => Err(format!("--order has {} cards, at most {} are supported", n, u8::MAX as usize + 1)),
+        _ => Ok(()),
     }
 }
 
-impl From<Vec<Card>> for HandOfFive {
-    fn from(cards: Vec<Card>) -> Self {
-        let mut card_counts: std::collections::HashMap<Card, u8> = std::collections::HashMap::new();
+fn parse_cards(cards: &str, ruleset: &Ruleset) -> Result<Vec<Card>, ParseError> {
+    cards.chars().map(|c| ruleset.card(c)).collect()
+}
 
-        for card in cards.iter() {
-            let count = card_counts.entry(*card).or_insert(0);
-            *count += 1;
+// A hand of any number of cards
+// Fields are in comparison order, so the derived ordering compares the category
+// first and the cards (by strength, in order of appearance) second
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {
+    category: Category,
+    cards: Vec<Card>,
+    /// how often each card occurs, largest first: [3, 2] is a full house
+    signature: Vec<u8>,
+}
+
+impl Hand {
+    fn new(cards: Vec<Card>, ruleset: &Ruleset) -> Result<Hand, ParseError> {
+        if cards.len() != ruleset.hand_size {
+            return Err(ParseError::WrongHandSize { expected: ruleset.hand_size, found: cards.len() });
         }
+        let signature = signature(&cards);
+        Ok(Hand { category: ruleset.category(&signature)?, cards, signature })
+    }
 
-        let mut card_counts: Vec<(Card, u8)> = card_counts.into_iter().collect();
-        card_counts.sort_by(|a, b| b.1.cmp(&a.1));
-
-        let mut card_counts: Vec<u8> = card_counts.into_iter().map(|(_, count)| count).collect();
-        card_counts.sort();
-        card_counts.reverse();
-
-        match card_counts.as_slice() {
-            [1, 1, 1, 1, 1] => HandOfFive::HighCard(cards),
-            [2, 1, 1, 1] => HandOfFive::OnePair(cards),
-            [2, 2, 1] => HandOfFive::TwoPair(cards),
-            [3, 1, 1] => HandOfFive::ThreeOfAKind(cards),
-            [3, 2] => HandOfFive::FullHouse(cards),
-            [4, 1] => HandOfFive::FourOfAKind(cards),
-            [5] => HandOfFive::FiveOfAKind(cards),
-            _ => panic!("Invalid card counts"),
+    /// The strongest hand the jokers among the cards can be turned into,
+    /// the cards as they are if the ruleset has no joker
+    fn with_jokers(cards: Vec<Card>, ruleset: &Ruleset) -> Result<Hand, ParseError> {
+        if ruleset.joker.is_none() {
+            return Hand::new(cards, ruleset);
         }
+        if cards.len() != ruleset.hand_size {
+            return Err(ParseError::WrongHandSize { expected: ruleset.hand_size, found: cards.len() });
+        }
+        let others: Vec<Card> = cards.iter().filter(|c| !ruleset.is_joker(c)).copied().collect();
+        // other cards by count, largest group first and ties going to the strongest card
+        let mut groups: Vec<(Card, u8)> = card_counts(&others).into_iter().collect();
+        groups.sort_by_key(|&(card, count)| std::cmp::Reverse((count, card)));
+
+        let replacements = match &ruleset.categories {
+            // a larger first group always wins under the default ranking,
+            // so all jokers join the largest group, a hand of only jokers
+            // becomes the strongest card there is
+            None => match groups.first().map(|&(card, _)| card).or_else(|| ruleset.substitutes().last().copied()) {
+                Some(best) => vec![best; cards.len() - others.len()],
+                None => return Hand::new(cards, ruleset),
+            },
+            // any ranking: the strongest category the jokers can reach
+            Some(categories) => categories
+                .iter()
+                .rev()
+                .find_map(|category| joker_replacements(&groups, category, ruleset))
+                .ok_or_else(|| ParseError::UnrankedSignature(signature(&cards)))?,
+        };
+        let mut replacements = replacements.into_iter();
+        let cards = cards
+            .iter()
+            .map(|c| if ruleset.is_joker(c) { replacements.next().unwrap() } else { *c })
+            .collect();
+        Hand::new(cards, ruleset)
     }
 }
 
-impl Ord for HandOfFive {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match (self, other) {
-            (HandOfFive::HighCard(cards), HandOfFive::HighCard(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::HighCard(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::HighCard(_)) => std::cmp::Ordering::Greater,
-
-            (HandOfFive::OnePair(cards), HandOfFive::OnePair(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::OnePair(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::OnePair(_)) => std::cmp::Ordering::Greater,
-
-            // Repeat for other hand types...
-            (HandOfFive::TwoPair(cards), HandOfFive::TwoPair(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::TwoPair(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::TwoPair(_)) => std::cmp::Ordering::Greater,
-
-            (HandOfFive::ThreeOfAKind(cards), HandOfFive::ThreeOfAKind(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::ThreeOfAKind(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::ThreeOfAKind(_)) => std::cmp::Ordering::Greater,
-
-            (HandOfFive::FullHouse(cards), HandOfFive::FullHouse(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::FullHouse(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::FullHouse(_)) => std::cmp::Ordering::Greater,
-
-            (HandOfFive::FourOfAKind(cards), HandOfFive::FourOfAKind(other_cards)) => cards.cmp(other_cards),
-            (HandOfFive::FourOfAKind(_), _) => std::cmp::Ordering::Less,
-            (_, HandOfFive::FourOfAKind(_)) => std::cmp::Ordering::Greater,
-
-            (HandOfFive::FiveOfAKind(cards), HandOfFive::FiveOfAKind(other_cards)) => cards.cmp(other_cards),
-            //(HandOfFive::FiveOfAKind(_), _) => std::cmp::Ordering::Greater,
-            //(_, HandOfFive::FiveOfAKind(_)) => std::cmp::Ordering::Less,
+/// The cards the jokers stand in for to turn a hand with the given groups of other cards
+/// (largest first) into the target signature, None if it cannot be reached. The i-th largest
+/// group grows into the i-th largest count, which works for some assignment exactly if it
+/// works for this one. Counts left over become groups of cards not in the hand, strongest first.
+fn joker_replacements(groups: &[(Card, u8)], target: &[u8], ruleset: &Ruleset) -> Option<Vec<Card>> {
+    if target.len() < groups.len() || groups.iter().zip(target).any(|(&(_, count), &size)| size < count) {
+        return None;
+    }
+    let mut unused = ruleset
+        .substitutes()
+        .into_iter()
+        .rev()
+        .filter(|card| groups.iter().all(|(c, _)| c != card));
+    let mut replacements = Vec::new();
+    for (i, &size) in target.iter().enumerate() {
+        let (card, count) = match groups.get(i) {
+            Some(&group) => group,
+            None => (unused.next()?, 0),
+        };
+        replacements.extend(std::iter::repeat_n(card, (size - count) as usize));
+    }
+    Some(replacements)
+}
+
+/// How often each card occurs, largest count first
+fn signature(cards: &[Card]) -> Vec<u8> {
+    let mut signature: Vec<u8> = card_counts(cards).into_values().collect();
+    signature.sort();
+    signature.reverse();
+    signature
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for card in &self.cards {
+            write!(f, "{}", card)?;
         }
+        write!(f, " ({})", category_name(&self.signature))
     }
 }
 
+/// How often each card occurs
+fn card_counts(cards: &[Card]) -> std::collections::HashMap<Card, u8> {
+    let mut card_counts: std::collections::HashMap<Card, u8> = std::collections::HashMap::new();
 
+    for card in cards.iter() {
+        let count = card_counts.entry(*card).or_insert(0);
+        *count += 1;
+    }
+    card_counts
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[derive(Debug, Clone)]
 struct BiddingHand {
-    hand: HandOfFive,
-    strongest_variation: HandOfFive,
+    cards: Vec<Card>,
+    strongest_variation: Hand,
     bid: u64,
 }
 
+impl BiddingHand {
+    /// Parses "32T3K 765" under the given ruleset
+    fn parse(line: &str, ruleset: &Ruleset) -> Result<BiddingHand, ParseError> {
+        let mut parts = line.split_whitespace();
+        let (cards, bid) = match (parts.next(), parts.next().map(|bid| bid.parse::<u64>()), parts.next()) {
+            (Some(cards), Some(Ok(bid)), None) => (cards, bid),
+            _ => return Err(ParseError::InvalidLine(line.to_string())),
+        };
+        let cards = parse_cards(cards, ruleset)?;
+        Ok(BiddingHand {
+            strongest_variation: Hand::with_jokers(cards.clone(), ruleset)?,
+            cards,
+            bid,
+        })
+    }
+}
+
 impl Ord for BiddingHand {
+    /// The type of the strongest variation decides, ties are broken
+    /// by the original cards, where jokers keep their own (weak) strength
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match self.strongest_variation.cmp(&other.strongest_variation) {
-            std::cmp::Ordering::Equal => self.hand.cmp(&other.hand),
-            other => other,
-        }
+        (&self.strongest_variation.category, &self.cards)
+            .cmp(&(&other.strongest_variation.category, &other.cards))
+    }
+}
+
+// equal exactly when the ordering says so, the bid does not take part
+impl PartialEq for BiddingHand {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for BiddingHand {}
+
+impl PartialOrd for BiddingHand {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -197,82 +336,332 @@ impl std::fmt::Display for BiddingHand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "BiddingHand {{\n  Cards: {:?} \n  Best: {:?}\n  Bid: {}\n}}\n",
-            self.hand, self.strongest_variation, self.bid
+            "BiddingHand {{\n  Cards: {} \n  Best: {}\n  Bid: {}\n}}\n",
+            self.cards.iter().map(|c| c.to_string()).collect::<String>(),
+            self.strongest_variation,
+            self.bid
         )
     }
 }
 
+/// Ranks all hands under the ruleset and sums up rank * bid
+fn total_winnings(file_str: &str, ruleset: &Ruleset) -> Result<u64, ParseError> {
+    let mut bidding_hands: Vec<BiddingHand> = file_str
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| BiddingHand::parse(line, ruleset))
+        .collect::<Result<Vec<BiddingHand>, ParseError>>()?;
+
+    bidding_hands.sort();
+    let game_ranks = 1..=bidding_hands.len();
+
+    let ranked_hands: Vec<(usize, BiddingHand)> = game_ranks
+        .zip(bidding_hands)
+        .collect();
+
+    let mut total_win: u64 = 0;
+    for (rank, hand) in ranked_hands {
+        println!("{}: {}", rank, hand);
+        total_win += rank as u64 * hand.bid;
+    }
+    Ok(total_win)
+}
+
 fn main() -> std::io::Result<()> {
     let input_file = "sample.txt";
 
+    // part one and two are always played, an additional ruleset can be given
+    // as `--order <cards from weakest to strongest>` and optionally `--joker <card>`.
+    // `--hand-size <n>` plays hands of n cards and `--categories "1,1,1;2,1;3"` ranks
+    // the count signatures from weakest to strongest, both apply to all rulesets
+    let mut rulesets = vec![Ruleset::standard(), Ruleset::jokers_wild()];
+    let mut custom_order = None;
+    let mut joker = None;
+    let mut hand_size = 5;
+    let mut categories = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let result = match (arg.as_str(), args.next()) {
+            ("--order", Some(order)) => {
+                custom_order = Some(order);
+                Ok(())
+            },
+            ("--joker", Some(card)) if card.chars().count() == 1 => {
+                joker = card.chars().next();
+                Ok(())
+            },
+            ("--hand-size", Some(value)) => match value.parse::<usize>() {
+                Ok(n) if n > 0 && n <= u8::MAX as usize => {
+                    hand_size = n;
+                    Ok(())
+                },
+                _ => Err(format!("invalid hand size {:?}", value)),
+            },
+            ("--categories", Some(value)) => {
+                categories = Some(value);
+                Ok(())
+            },
+            _ => Err(format!("invalid argument {:?}", arg)),
+        };
+        result.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
+    if let Some(order) = &custom_order {
+        check_order(order).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    }
+    match (custom_order, joker) {
+        (Some(order), Some(card)) if !order.contains(card) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("joker {:?} is not one of the cards in --order {:?}", card, order),
+            ))
+        }
+        (Some(order), joker) => rulesets.push(Ruleset::custom("custom", &order, joker)),
+        (None, Some(_)) => {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "--joker needs --order"))
+        }
+        (None, None) => {}
+    }
+    let categories = categories
+        .map(|value| parse_categories(&value, hand_size))
+        .transpose()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let rulesets: Vec<Ruleset> = rulesets
+        .into_iter()
+        .map(|ruleset| Ruleset { hand_size, categories: categories.clone(), ..ruleset })
+        .collect();
+
     println!("Input file: {:?}", input_file);
 
     // read file line by line
     let file_str = read_to_string(input_file)?;
 
-    let cards_bids_tuple_vec: Vec<(&str, &str)> = file_str
-        .lines()
-        .filter_map(|line| {
-            let mut parts = line.split_whitespace();
-            if let (Some(cards_str), Some(bid_str)) = (parts.next(), parts.next()) {
-                Some((cards_str, bid_str))
-            } else {
-                None
-            }
-        })
-        .collect();
+    let mut results = Vec::new();
+    for ruleset in &rulesets {
+        let total_win = total_winnings(&file_str, ruleset)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        results.push((ruleset, total_win));
+    }
 
-    let (hands_str, bids_str): (Vec<&str>, Vec<&str>) = cards_bids_tuple_vec.into_iter().unzip();
+    for (ruleset, total_win) in results {
+        println!("Total winnings ({}): {}", ruleset.name, total_win);
+    }
 
-    let mut bidding_hands: Vec<BiddingHand> = Vec::new();
+    Ok(())
+}
 
-    for (hand, bid) in hands_str.iter().zip(bids_str.iter()) {
-        // create a BiddingHand for each line
-        let hand = HandOfFive::from(hand.chars().collect::<Vec<char>>());
-        bidding_hands.push(BiddingHand {
-            hand: hand.clone(),
-            strongest_variation: hand.apply_jokers(),
-            bid: bid.parse::<u64>().unwrap(),
-        });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every hand the jokers can be turned into, one substitution at a time. Jokers are
+    /// interchangeable for the category, so later jokers only take variants from `from` on
+    fn replace_jokers(hand_cards: Vec<Card>, variants: &[Card], ruleset: &Ruleset, i: usize, from: usize) -> Vec<Vec<Card>> {
+        if i >= hand_cards.len() {
+            return vec![hand_cards];
+        }
+
+        if !ruleset.is_joker(&hand_cards[i]) {
+            return replace_jokers(hand_cards, variants, ruleset, i + 1, from);
+        }
+
+        let mut hands = Vec::new();
+        for (v, variant) in variants.iter().enumerate().skip(from) {
+            let mut new_hand = hand_cards[0..i].to_vec();
+            new_hand.push(*variant);
+            new_hand.extend_from_slice(&hand_cards[i+1..]);
+            hands.extend(replace_jokers(new_hand, variants, ruleset, i + 1, v));
+        }
+        hands
     }
 
-    bidding_hands.sort();
-    let game_ranks = 1..=bidding_hands.len();
+    /// Best category over all substitutions
+    fn enumerated_category(cards: &[Card], ruleset: &Ruleset) -> Category {
+        replace_jokers(cards.to_vec(), &ruleset.substitutes(), ruleset, 0, 0)
+            .into_iter()
+            .filter_map(|cards| Hand::new(cards, ruleset).ok())
+            .map(|hand| hand.category)
+            .max()
+            .unwrap()
+    }
 
+    /// Random hands of `size` cards of the ruleset, the same ones on every run
+    fn sample_hands(ruleset: &Ruleset, size: usize, count: usize) -> Vec<Vec<Card>> {
+        // xorshift64 with a fixed seed
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let cards: String =
+                    (0..size).map(|_| ruleset.order[(next() % ruleset.order.len() as u64) as usize]).collect();
+                parse_cards(&cards, ruleset).unwrap()
+            })
+            .collect()
+    }
 
-    let ranked_hands: Vec<(usize, BiddingHand)> = game_ranks
-        .into_iter()
-        .zip(bidding_hands.into_iter())
-        .collect();
+    /// Hands of the sample that hold a joker and a few edge cases
+    fn joker_hands(ruleset: &Ruleset, count: usize) -> Vec<Vec<Card>> {
+        ["JJJJJ", "JJJJ2", "AJJJJ", "J2345", "2J3J4", "KKJQQ", "JKKKJ", "QJJQ2", "T55J5"]
+            .map(|cards| parse_cards(cards, ruleset).unwrap())
+            .into_iter()
+            .chain(sample_hands(ruleset, 5, count))
+            .filter(|cards| cards.iter().any(|c| ruleset.is_joker(c)))
+            .collect()
+    }
 
-    let mut total_win: u64 = 0;
-    for (rank, hand) in ranked_hands {
-        println!("{}: {}", rank, hand);
-        total_win += rank as u64 * hand.bid;
+    #[test]
+    fn jokers_match_enumeration() {
+        let ruleset = Ruleset::jokers_wild();
+        let hands = joker_hands(&ruleset, 8000);
+        assert!(hands.len() > 2000);
+        for cards in hands {
+            assert_eq!(
+                Hand::with_jokers(cards.clone(), &ruleset).unwrap().category,
+                enumerated_category(&cards, &ruleset),
+                "{:?}",
+                cards
+            );
+        }
     }
 
-    println!("Total winnings: {}", total_win);
+    fn with_categories(categories: &str) -> Ruleset {
+        Ruleset { categories: Some(parse_categories(categories, 5).unwrap()), ..Ruleset::jokers_wild() }
+    }
 
-    Ok(())
-}
+    #[test]
+    fn jokers_match_enumeration_for_shuffled_categories() {
+        let mut shuffled = "1,1,1,1,1;2,1,1,1;2,2,1;3,1,1;3,2;4,1;5".split(';').collect::<Vec<_>>();
+        for round in 0..6 {
+            // a different, fixed order of the categories every round
+            shuffled.rotate_left(3);
+            shuffled.swap(round, 6 - round);
+            let ruleset = with_categories(&shuffled.join(";"));
+            for cards in joker_hands(&ruleset, 600) {
+                assert_eq!(
+                    Hand::with_jokers(cards.clone(), &ruleset).unwrap().category,
+                    enumerated_category(&cards, &ruleset),
+                    "{:?} {:?}",
+                    cards,
+                    ruleset.categories
+                );
+            }
+        }
+    }
 
+    #[test]
+    fn larger_groups_ranking_lower() {
+        // three of a kind ranks right above high card, so the joker should make two pair
+        let ruleset = with_categories("1,1,1,1,1;3,1,1;2,1,1,1;2,2,1;3,2;4,1;5");
+        let best = Hand::with_jokers(parse_cards("AAJ23", &ruleset).unwrap(), &ruleset).unwrap();
+        assert_eq!(best.signature, vec![2, 2, 1]);
+        assert_eq!(total_winnings("AAJ23 10\nKK456 1\n", &ruleset), Ok(21));
+    }
 
-fn replace_jokers(hand_cards: Vec<Card>, variants: Vec<Card>, i: usize) -> Vec<Vec<Card>> {
-    if i >= hand_cards.len() {
-        return vec![hand_cards];
+    #[test]
+    fn jokers_avoid_unranked_categories() {
+        // no pairs at all, the joker has to become a card that is not in the hand yet
+        let ruleset = with_categories("1,1,1,1,1;3,1,1;3,2;4,1;5");
+        let best = Hand::with_jokers(parse_cards("AJ234", &ruleset).unwrap(), &ruleset).unwrap();
+        assert_eq!(best.signature, vec![1, 1, 1, 1, 1]);
+        let best = Hand::with_jokers(parse_cards("AAJ23", &ruleset).unwrap(), &ruleset).unwrap();
+        assert_eq!(best.signature, vec![3, 1, 1]);
+        // nothing reaches a full house or better from two pair without a joker
+        assert_eq!(
+            Hand::with_jokers(parse_cards("AA223", &ruleset).unwrap(), &ruleset),
+            Err(ParseError::UnrankedSignature(vec![2, 2, 1]))
+        );
     }
 
-    if hand_cards[i] != Card::J {
-        return replace_jokers(hand_cards, variants, i + 1);
+    #[test]
+    fn custom_orders_are_checked() {
+        assert_eq!(check_order("23456789TJQKA"), Ok(()));
+        assert!(check_order("23456789TJQKA2").unwrap_err().contains("'2' appears more than once"));
+        let labels = |n: u32| (0..n).map(|i| char::from_u32(0x100 + i).unwrap()).collect::<String>();
+        assert_eq!(check_order(&labels(256)), Ok(()));
+        assert!(check_order(&labels(257)).unwrap_err().contains("257 cards"));
+        // the strongest of 256 cards still gets its own rank
+        let ruleset = Ruleset::custom("wide", &labels(256), None);
+        let last = char::from_u32(0x100 + 255).unwrap();
+        assert_eq!(ruleset.card(last).map(|card| card.rank), Ok(255));
     }
 
-    let mut hands = Vec::new();
-    for x in 2..=13 {
-        let mut new_hand = hand_cards[0..i].to_vec();
-        new_hand.push(variants[x-2].clone());
-        new_hand.extend_from_slice(&hand_cards[i+1..]);
-        hands.extend(replace_jokers(new_hand, variants.clone(), i + 1));
+    #[test]
+    fn sample() {
+        let sample = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483\n";
+        assert_eq!(total_winnings(sample, &Ruleset::standard()), Ok(6440));
+        assert_eq!(total_winnings(sample, &Ruleset::jokers_wild()), Ok(5905));
     }
-    hands
-}
\ No newline at end of file
+
+    fn hand(cards: &str, ruleset: &Ruleset) -> Hand {
+        Hand::with_jokers(parse_cards(cards, ruleset).unwrap(), ruleset).unwrap()
+    }
+
+    #[test]
+    fn three_card_hands_rank_by_signature() {
+        let ruleset = Ruleset { hand_size: 3, ..Ruleset::standard() };
+        let (three, pair, high) = (hand("222", &ruleset), hand("AA2", &ruleset), hand("AKQ", &ruleset));
+        assert_eq!(three.signature, vec![3]);
+        assert_eq!(pair.signature, vec![2, 1]);
+        assert_eq!(high.signature, vec![1, 1, 1]);
+        assert!(three > pair && pair > high);
+        // within a category the cards decide
+        assert!(hand("333", &ruleset) > three);
+        assert!(hand("A22", &ruleset) > hand("2AA", &ruleset));
+    }
+
+    #[test]
+    fn six_card_hands_rank_by_signature() {
+        let ruleset = Ruleset { hand_size: 6, ..Ruleset::standard() };
+        // three of a kind beats three pairs, whatever the cards
+        let three = hand("222345", &ruleset);
+        let three_pairs = hand("AAKKQQ", &ruleset);
+        assert_eq!(three.signature, vec![3, 1, 1, 1]);
+        assert_eq!(three_pairs.signature, vec![2, 2, 2]);
+        assert!(three > three_pairs);
+        assert!(three_pairs > hand("AAKKQJ", &ruleset));
+        assert!(hand("222333", &ruleset) > three);
+        assert!(hand("22223A", &ruleset) > hand("AAAKKK", &ruleset));
+    }
+
+    #[test]
+    fn three_card_winnings() {
+        let hands = "32T 10\nKKJ 20\nJJJ 5\nA2A 7\n";
+        // 32T, KKJ, A2A, JJJ
+        assert_eq!(total_winnings(hands, &Ruleset { hand_size: 3, ..Ruleset::standard() }), Ok(91));
+        // 32T, A2A, JJJ, KKJ: both three of a kind, the K is stronger than a joker
+        assert_eq!(total_winnings(hands, &Ruleset { hand_size: 3, ..Ruleset::jokers_wild() }), Ok(119));
+        assert_eq!(
+            total_winnings("32TK 1\n", &Ruleset { hand_size: 3, ..Ruleset::standard() }),
+            Err(ParseError::WrongHandSize { expected: 3, found: 4 })
+        );
+    }
+
+    #[test]
+    fn large_hands_rank_by_signature() {
+        let ruleset = Ruleset { hand_size: 255, ..Ruleset::jokers_wild() };
+        let hands: Vec<Hand> = sample_hands(&ruleset, 255, 50)
+            .into_iter()
+            .map(|cards| Hand::with_jokers(cards, &ruleset).unwrap())
+            .collect();
+        assert!(hands.iter().all(|hand| hand.signature.iter().map(|&c| c as usize).sum::<usize>() == 255));
+
+        let cards = |groups: &[(char, usize)]| -> String { groups.iter().map(|&(c, n)| c.to_string().repeat(n)).collect() };
+        // the jokers all join the 2s
+        let all_twos = hand(&cards(&[('J', 254), ('2', 1)]), &ruleset);
+        assert_eq!(all_twos.signature, vec![255]);
+        let aces = hand(&cards(&[('A', 254), ('K', 1)]), &ruleset);
+        assert_eq!(aces.signature, vec![254, 1]);
+        assert!(all_twos > aces);
+        let split = hand(&cards(&[('A', 128), ('K', 127)]), &ruleset);
+        let split_more = hand(&cards(&[('A', 128), ('K', 126), ('Q', 1)]), &ruleset);
+        assert!(aces > split && split > split_more);
+        let mut sorted = vec![split_more.clone(), all_twos.clone(), split.clone(), aces.clone()];
+        sorted.sort();
+        assert_eq!(sorted, vec![split_more, split, aces, all_twos]);
+    }
+}